@@ -0,0 +1,138 @@
+use std::num::NonZeroU32;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dragon::{Stat, StatStages},
+    effect::{effects::StatStageModifier, EffectContext, LongTermEffectTrait},
+    events::MoveEvent,
+    moves::AttackContext,
+    party::RelativePartyId,
+};
+
+/// A passive ability, as loaded from data. The species lists the names
+/// of the abilities it may have, and each dragon has one of them.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AbilityData {
+    pub name: String,
+    pub effects: Vec<AbilityEffect>,
+}
+
+impl AbilityData {
+    pub fn new(name: &str, effects: Vec<AbilityEffect>) -> Self {
+        Self {
+            name: name.to_string(),
+            effects,
+        }
+    }
+}
+
+/// One part of what an ability does.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum AbilityEffect {
+    /// Changes a stat stage of the opponent when the dragon enters
+    /// the battle.
+    OpponentStageOnEntry { stat: Stat, amount: i8 },
+    /// Changes a stat stage of the dragon for as long as it battles.
+    Stage { stat: Stat, amount: i8 },
+    /// Multiplies the base power of the dragon's moves by
+    /// `numerator / denominator`.
+    PowerMultiplier {
+        numerator: u32,
+        denominator: NonZeroU32,
+    },
+    /// Multiplies the base power of moves used against the dragon
+    /// by `numerator / denominator`.
+    DefendingPowerMultiplier {
+        numerator: u32,
+        denominator: NonZeroU32,
+    },
+    /// Makes the dragon immune to contact moves.
+    ContactImmunity,
+    /// Makes the dragon immune to the effects with the given names.
+    EffectImmunity { effects: Vec<String> },
+}
+
+/// The effect attaching an ability to a dragon. It can not be removed
+/// by cures.
+pub struct Ability(AbilityData);
+
+impl Ability {
+    pub fn new(data: AbilityData) -> Self {
+        Self(data)
+    }
+
+    pub fn data(&self) -> &AbilityData {
+        &self.0
+    }
+}
+
+impl LongTermEffectTrait for Ability {
+    fn get_name(&self) -> &str {
+        "ability"
+    }
+
+    fn removable(&self) -> bool {
+        false
+    }
+
+    fn on_switch_in(&mut self, ctx: &mut EffectContext) {
+        for effect in &self.0.effects {
+            if let AbilityEffect::OpponentStageOnEntry { stat, amount } = *effect {
+                ctx.emit(MoveEvent::Effected {
+                    rel_party_id: RelativePartyId::Opposing,
+                    description: self.0.name.clone(),
+                });
+                ctx.add_effect(
+                    RelativePartyId::Opposing,
+                    Box::new(StatStageModifier::new(stat, amount)),
+                );
+            }
+        }
+    }
+
+    fn stat_calculation(&self, stages: StatStages) -> StatStages {
+        self.0.effects.iter().fold(stages, |s, e| match *e {
+            AbilityEffect::Stage { stat, amount } => s.modify(stat, amount),
+            _ => s,
+        })
+    }
+
+    fn offending(&self, mut attack: AttackContext) -> Option<AttackContext> {
+        for effect in &self.0.effects {
+            if let AbilityEffect::PowerMultiplier {
+                numerator,
+                denominator,
+            } = *effect
+            {
+                attack.move_stats.base_power =
+                    attack.move_stats.base_power.saturating_mul(numerator) / denominator;
+            }
+        }
+        Some(attack)
+    }
+
+    fn defending(&self, mut attack: AttackContext) -> Option<AttackContext> {
+        for effect in &self.0.effects {
+            match *effect {
+                AbilityEffect::DefendingPowerMultiplier {
+                    numerator,
+                    denominator,
+                } => {
+                    attack.move_stats.base_power =
+                        attack.move_stats.base_power.saturating_mul(numerator) / denominator;
+                }
+                AbilityEffect::ContactImmunity if attack.flags.contact => return None,
+                _ => {}
+            }
+        }
+        Some(attack)
+    }
+
+    fn accepts_effect(&self, effect_name: &str) -> bool {
+        !self.0.effects.iter().any(|e| match e {
+            AbilityEffect::EffectImmunity { effects } => effects.iter().any(|n| n == effect_name),
+            _ => false,
+        })
+    }
+}
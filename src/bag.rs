@@ -0,0 +1,118 @@
+use std::{collections::HashMap, num::NonZeroU32};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{effect::EffectCategory, party::PartyItem};
+
+/// An item that can be used from the bag during a battle, as loaded
+/// from data.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BagItemData {
+    pub name: String,
+    pub effects: Vec<BagItemEffect>,
+}
+
+impl BagItemData {
+    pub fn new(name: &str, effects: Vec<BagItemEffect>) -> Self {
+        Self {
+            name: name.to_string(),
+            effects,
+        }
+    }
+
+    /// Uses the item on a party member. Returns false if the item had
+    /// no effect, in which case it should not be used up.
+    pub fn use_on(&self, target: &mut PartyItem) -> bool {
+        let mut used = false;
+        for effect in &self.effects {
+            used |= match *effect {
+                BagItemEffect::Heal { amount } => target.heal(amount) > 0,
+                BagItemEffect::CureStatus => target.remove_category(EffectCategory::Status),
+                BagItemEffect::RestorePp { amount } => target.restore_pp(amount),
+                BagItemEffect::Revive { denominator } => {
+                    target.revive(target.dragon.max_hp() / denominator)
+                }
+            };
+        }
+        used
+    }
+}
+
+/// One part of what a bag item does to the party member it is used on.
+/// Denominators of zero are rejected when the data is read.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum BagItemEffect {
+    /// Restores `amount` HP. Does not work on fainted dragons.
+    Heal { amount: u32 },
+    /// Removes the status conditions of the dragon.
+    CureStatus,
+    /// Restores `amount` PP of every move of the dragon.
+    RestorePp { amount: u8 },
+    /// Brings a fainted dragon back with `1 / denominator` of its
+    /// maximum HP.
+    Revive { denominator: NonZeroU32 },
+}
+
+/// The bag items owned by one side of the battle.
+#[derive(Default)]
+pub struct Inventory {
+    items: HashMap<String, (BagItemData, u32)>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self {
+            items: HashMap::new(),
+        }
+    }
+
+    /// Adds `count` pieces of an item to the inventory.
+    pub fn add(&mut self, item: BagItemData, count: u32) {
+        self.items.entry(item.name.clone()).or_insert((item, 0)).1 += count;
+    }
+
+    /// Returns how many pieces of the item are in the inventory.
+    pub fn count(&self, name: &str) -> u32 {
+        self.items.get(name).map_or(0, |(_, count)| *count)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BagItemData> {
+        self.items
+            .get(name)
+            .filter(|(_, count)| *count > 0)
+            .map(|(item, _)| item)
+    }
+
+    /// Removes one piece of the item. Returns false if there was none.
+    pub fn remove_one(&mut self, name: &str) -> bool {
+        match self.items.get_mut(name) {
+            Some((_, count)) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which bag items may be used in a battle.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub enum BagRules {
+    /// Every item may be used.
+    #[default]
+    Any,
+    /// No items may be used.
+    Forbidden,
+    /// Only the items with the given names may be used.
+    Only(Vec<String>),
+}
+
+impl BagRules {
+    pub fn allows(&self, name: &str) -> bool {
+        match self {
+            BagRules::Any => true,
+            BagRules::Forbidden => false,
+            BagRules::Only(names) => names.iter().any(|n| n == name),
+        }
+    }
+}
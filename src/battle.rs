@@ -1,20 +1,115 @@
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, SeedableRng};
+
 use crate::{
-    events::MoveEvent,
-    moves::{MoveResult, MoveTrait},
-    party::{Party, PartyId},
+    bag::{BagRules, Inventory},
+    damage::{
+        self, ClassicDamageCalculator, CritRules, DamageBreakdown, DamageCalculator, DamagePreview,
+    },
+    dragon::{DragonData, DragonIdentity, Stats},
+    effect::{EffectAction, EffectCategory, EffectContext, LongTermEffectTrait},
+    error::{EngineError, IllegalAction, Result},
+    events::{MoveEvent, ProgressEvent},
+    moves::{MoveContext, MoveResult, MoveTrait},
+    party::{Party, PartyId, PartyItem, RelativePartyId},
+    side::{self, SideEffects, WeatherData},
 };
 
+/// An action a party chooses for the next turn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Use the move in the given move slot of the active dragon.
+    Move(usize),
+    /// Switch the active dragon to the party member at the given index.
+    Switch(usize),
+    /// Use an item from the inventory on the party member at `target`.
+    UseItem { item: String, target: usize },
+}
+
+/// A move of the active dragon, as returned by [`Battlefield::legal_actions`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveOption {
+    pub slot: usize,
+    pub name: String,
+    pub pp: u8,
+    pub max_pp: u8,
+    /// The name of the effect forbidding the move, such as "disable"
+    /// or "taunt".
+    pub blocked_by: Option<String>,
+}
+
+impl MoveOption {
+    /// Returns true if the move may be chosen.
+    pub fn is_usable(&self) -> bool {
+        self.pp > 0 && self.blocked_by.is_none()
+    }
+}
+
+/// The actions a party may currently choose, apart from bag items.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegalActions {
+    /// Every move of the active dragon. Moves which may not be chosen
+    /// are included, so that they can be shown as such.
+    pub moves: Vec<MoveOption>,
+    /// The party members the party may switch to.
+    pub switches: Vec<usize>,
+    /// The move slot the active dragon has to use, if an effect forces
+    /// one. The move is used no matter which action is submitted.
+    pub forced_move: Option<usize>,
+}
+
 pub struct Battlefield<T: Messenger> {
     parties: (Party, Party),
+    sides: (SideEffects, SideEffects),
+    inventories: (Inventory, Inventory),
+    bag_rules: BagRules,
+    crit_rules: CritRules,
+    damage_calculator: Box<dyn DamageCalculator>,
+    pending: (Option<Action>, Option<Action>),
+    /// The members of each party which have been active while the
+    /// active dragon of the opposing party was.
+    participants: (Vec<usize>, Vec<usize>),
+    experience_gain: bool,
     messenger: T,
+    rng: StdRng,
 }
 
 impl<T: Messenger> Battlefield<T> {
-    pub fn new(party_a: Party, party_b: Party, msg: T) -> Self {
-        Self {
+    /// Creates a battlefield, letting the active dragons of both parties
+    /// enter the battle. Fails if one of the parties is empty.
+    pub fn new(party_a: Party, party_b: Party, msg: T) -> Result<Self> {
+        Self::new_with_rng(party_a, party_b, msg, StdRng::from_entropy())
+    }
+
+    /// Creates a battlefield where every random event is decided by a
+    /// generator seeded with `seed`, making the battle repeatable.
+    pub fn new_seeded(party_a: Party, party_b: Party, msg: T, seed: u64) -> Result<Self> {
+        Self::new_with_rng(party_a, party_b, msg, StdRng::seed_from_u64(seed))
+    }
+
+    fn new_with_rng(party_a: Party, party_b: Party, msg: T, rng: StdRng) -> Result<Self> {
+        if party_a.is_empty() || party_b.is_empty() {
+            return Err(EngineError::EmptyParty);
+        }
+        let (party_a_active, party_b_active) = (party_a.active_index(), party_b.active_index());
+        let mut battlefield = Self {
             parties: (party_a, party_b),
+            sides: (SideEffects::new(), SideEffects::new()),
+            inventories: (Inventory::new(), Inventory::new()),
+            bag_rules: BagRules::default(),
+            crit_rules: CritRules::default(),
+            damage_calculator: Box::new(ClassicDamageCalculator::default()),
+            participants: (vec![party_a_active], vec![party_b_active]),
+            experience_gain: false,
+            pending: (None, None),
             messenger: msg,
+            rng,
+        };
+        for party_id in battlefield.speed_order() {
+            battlefield.enter_battle(party_id);
         }
+        Ok(battlefield)
     }
 
     pub fn party(&self, id: PartyId) -> &Party {
@@ -31,6 +126,15 @@ impl<T: Messenger> Battlefield<T> {
         }
     }
 
+    pub fn messenger(&self) -> &T {
+        &self.messenger
+    }
+
+    /// Gets the identity of the active dragon of a party.
+    pub fn identity(&self, id: PartyId) -> &DragonIdentity {
+        self.party(id).active().dragon.identity()
+    }
+
     pub fn party_and_opposing(&self, id: PartyId) -> (&Party, &Party) {
         (self.party(id), self.party(id.opposing()))
     }
@@ -42,35 +146,706 @@ impl<T: Messenger> Battlefield<T> {
         }
     }
 
-    fn party_opposing_messenger_mut(&mut self, id: PartyId) -> (&mut Party, &mut Party, &T) {
+    /// Gets the side effects of a party.
+    pub fn side(&self, id: PartyId) -> &SideEffects {
+        match id {
+            PartyId::Party1 => &self.sides.0,
+            PartyId::Party2 => &self.sides.1,
+        }
+    }
+
+    pub fn side_mut(&mut self, id: PartyId) -> &mut SideEffects {
+        match id {
+            PartyId::Party1 => &mut self.sides.0,
+            PartyId::Party2 => &mut self.sides.1,
+        }
+    }
+
+    /// Gets the bag inventory of a party.
+    pub fn inventory(&self, id: PartyId) -> &Inventory {
         match id {
-            PartyId::Party1 => (&mut self.parties.0, &mut self.parties.1, &self.messenger),
-            PartyId::Party2 => (&mut self.parties.1, &mut self.parties.0, &self.messenger),
+            PartyId::Party1 => &self.inventories.0,
+            PartyId::Party2 => &self.inventories.1,
+        }
+    }
+
+    pub fn inventory_mut(&mut self, id: PartyId) -> &mut Inventory {
+        match id {
+            PartyId::Party1 => &mut self.inventories.0,
+            PartyId::Party2 => &mut self.inventories.1,
+        }
+    }
+
+    /// Sets which bag items may be used in this battle.
+    pub fn set_bag_rules(&mut self, rules: BagRules) {
+        self.bag_rules = rules;
+    }
+
+    pub fn bag_rules(&self) -> &BagRules {
+        &self.bag_rules
+    }
+
+    /// Sets the rules of critical hits in this battle.
+    pub fn set_crit_rules(&mut self, rules: CritRules) {
+        self.crit_rules = rules;
+    }
+
+    pub fn crit_rules(&self) -> &CritRules {
+        &self.crit_rules
+    }
+
+    /// Replaces the damage formula of this battle.
+    pub fn set_damage_calculator(&mut self, calculator: Box<dyn DamageCalculator>) {
+        self.damage_calculator = calculator;
+    }
+
+    pub fn damage_calculator(&self) -> &dyn DamageCalculator {
+        self.damage_calculator.as_ref()
+    }
+
+    /// Lets the participants of a party gain experience once an opposing
+    /// dragon faints. Off by default.
+    pub fn set_experience_gain(&mut self, experience_gain: bool) {
+        self.experience_gain = experience_gain;
+    }
+
+    fn participants_mut(&mut self, id: PartyId) -> &mut Vec<usize> {
+        match id {
+            PartyId::Party1 => &mut self.participants.0,
+            PartyId::Party2 => &mut self.participants.1,
+        }
+    }
+
+    /// Changes the active dragon of a party into a form for the rest of
+    /// the battle, see [`BattleDragon::change_form`](crate::dragon::BattleDragon::change_form).
+    pub fn change_form(&mut self, party_id: PartyId, form: Arc<DragonData>) {
+        let message = format!("changed into {}", form.name);
+        self.party_mut(party_id)
+            .active_mut()
+            .dragon
+            .change_form(form);
+        self.messenger
+            .on_message(self, party_id, self.identity(party_id), &message);
+    }
+
+    /// Ends the battle, giving back the parties. Every dragon goes back
+    /// to its own form.
+    pub fn into_parties(self) -> (Party, Party) {
+        let (mut party_a, mut party_b) = self.parties;
+        for party in [&mut party_a, &mut party_b] {
+            for item in party.items.iter_mut() {
+                item.dragon.revert_form();
+            }
+        }
+        (party_a, party_b)
+    }
+
+    /// Sets the weather of the battlefield, replacing the previous one.
+    pub fn set_weather(&mut self, weather: WeatherData) {
+        for side in [&mut self.sides.0, &mut self.sides.1] {
+            side.remove_effect("weather");
+            side.add_effect(Box::new(side::effects::Weather::new(weather.clone())));
+        }
+    }
+
+    fn pending_mut(&mut self, id: PartyId) -> &mut Option<Action> {
+        match id {
+            PartyId::Party1 => &mut self.pending.0,
+            PartyId::Party2 => &mut self.pending.1,
+        }
+    }
+
+    /// Returns the moves and switches the party may currently choose.
+    pub fn legal_actions(&self, party_id: PartyId) -> LegalActions {
+        let party = self.party(party_id);
+        let active = party.active();
+        let moves = active
+            .moves()
+            .iter()
+            .enumerate()
+            .map(|(slot, move_slot)| MoveOption {
+                slot,
+                name: move_slot.attack().get_name().to_string(),
+                pp: move_slot.pp,
+                max_pp: move_slot.max_pp,
+                blocked_by: active.blocking_effect(slot).map(str::to_string),
+            })
+            .collect();
+        let forced_move = active.forced_move();
+        let switches = match forced_move {
+            Some(_) => vec![],
+            None => (0..party.len())
+                .filter(|&index| party.check_switch(index).is_ok())
+                .collect(),
+        };
+        LegalActions {
+            moves,
+            switches,
+            forced_move,
+        }
+    }
+
+    fn check_action(
+        &self,
+        party_id: PartyId,
+        action: &Action,
+    ) -> std::result::Result<(), IllegalAction> {
+        let active = self.party(party_id).active();
+        if let Some(forced) = active.forced_move() {
+            return match action {
+                Action::Move(_) => Ok(()),
+                _ => Err(IllegalAction::MoveForced(forced)),
+            };
+        }
+        match action {
+            Action::Move(slot) => match active.moves().get(*slot) {
+                None => Err(IllegalAction::NoSuchMove(*slot)),
+                Some(move_slot) if move_slot.pp == 0 => Err(IllegalAction::NoPpLeft(*slot)),
+                Some(_) => match active.blocking_effect(*slot) {
+                    Some(effect) => Err(IllegalAction::MoveBlocked {
+                        slot: *slot,
+                        effect: effect.to_string(),
+                    }),
+                    None => Ok(()),
+                },
+            },
+            Action::Switch(next) => self.party(party_id).check_switch(*next),
+            Action::UseItem { item, target } => {
+                if self.party(party_id).member(*target).is_none() {
+                    Err(IllegalAction::NoSuchMember(*target))
+                } else if !self.bag_rules.allows(item) {
+                    Err(IllegalAction::ItemNotAllowed(item.clone()))
+                } else if self.inventory(party_id).get(item).is_none() {
+                    Err(IllegalAction::ItemNotOwned(item.clone()))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Chooses the action of a party for the next turn, replacing the
+    /// one chosen before. Fails if the action is not allowed, see
+    /// [`legal_actions`](Self::legal_actions). If the active dragon has
+    /// to use a move, any move is accepted, but only the forced one is used.
+    pub fn submit_action(&mut self, party_id: PartyId, action: Action) -> Result<()> {
+        self.check_action(party_id, &action)?;
+        *self.pending_mut(party_id) = Some(action);
+        Ok(())
+    }
+
+    /// Carries out the submitted actions, then ends the turn. Switches
+    /// and item uses go before moves. Within both groups, the party
+    /// with the faster active dragon acts first.
+    pub fn run_turn(&mut self) {
+        let mut actions = [
+            (PartyId::Party1, self.pending.0.take()),
+            (PartyId::Party2, self.pending.1.take()),
+        ];
+        if self.speed_order()[0] == PartyId::Party2 {
+            actions.swap(0, 1);
+        }
+        for (party_id, action) in actions.iter_mut() {
+            if let Some(slot) = self.party(*party_id).active().forced_move() {
+                *action = Some(Action::Move(slot));
+            }
+        }
+        for (party_id, action) in &actions {
+            match action {
+                Some(Action::Switch(next)) => {
+                    // The switch was checked when it was submitted, but
+                    // the battle may have changed since.
+                    if let Err(error) = self.switch(*party_id, *next) {
+                        self.messenger.on_message(
+                            self,
+                            *party_id,
+                            self.identity(*party_id),
+                            &error.to_string(),
+                        );
+                    }
+                }
+                Some(Action::UseItem { item, target }) => self.use_item(*party_id, item, *target),
+                _ => {}
+            }
+        }
+
+        let order = self.speed_order();
+        let mut moves: Vec<_> = actions
+            .iter()
+            .filter_map(|(party_id, action)| match action {
+                Some(Action::Move(slot)) => Some((*party_id, *slot)),
+                _ => None,
+            })
+            .collect();
+        moves.sort_by_key(|(party_id, _)| order.iter().position(|id| id == party_id));
+        for (party_id, slot) in moves {
+            if self.party(party_id).active().hp() > 0 {
+                self.use_move(party_id, slot);
+            }
+        }
+        self.turn();
+    }
+
+    /// Uses the move in a move slot of the active dragon, spending one
+    /// PP unless the move is forced by an effect.
+    fn use_move(&mut self, party_id: PartyId, slot: usize) {
+        let item = self.party_mut(party_id).active_mut();
+        let forced = item.forced_move() == Some(slot);
+        let has_pp = item.moves.get(slot).is_some_and(|m| m.pp > 0);
+        if !forced && has_pp && !item.can_use_move(slot) {
+            self.messenger.on_message(
+                self,
+                party_id,
+                self.identity(party_id),
+                "can not use this move",
+            );
+            return;
+        }
+        let item = self.party_mut(party_id).active_mut();
+        let attack = match item.moves.get_mut(slot) {
+            Some(move_slot) if forced || move_slot.pp > 0 => {
+                if !forced {
+                    move_slot.pp -= 1;
+                }
+                move_slot.attack.clone()
+            }
+            _ => {
+                self.messenger.on_message(
+                    self,
+                    party_id,
+                    self.identity(party_id),
+                    "has no PP left",
+                );
+                return;
+            }
+        };
+        self.attack(party_id, &*attack);
+    }
+
+    /// Uses a bag item of a party on one of its members.
+    fn use_item(&mut self, party_id: PartyId, name: &str, target: usize) {
+        let item = match self.inventory(party_id).get(name) {
+            Some(item) if self.bag_rules.allows(name) => item.clone(),
+            _ => return,
+        };
+        let used = match self.party_mut(party_id).member_mut(target) {
+            Some(member) => item.use_on(member),
+            None => false,
+        };
+        if used {
+            self.inventory_mut(party_id).remove_one(name);
+            let dragon = self
+                .party(party_id)
+                .member(target)
+                .unwrap()
+                .dragon
+                .identity();
+            self.messenger
+                .on_bag_item(self, party_id, dragon, name, target as u8);
+        } else {
+            self.messenger.on_message(
+                self,
+                party_id,
+                self.identity(party_id),
+                "used an item with no effect",
+            );
+        }
+    }
+
+    /// Calculates the final stats of the active dragon of a party,
+    /// including its own effects and the side effects of its party.
+    pub fn calc_stats(&self, id: PartyId) -> Stats {
+        self.side(id)
+            .calc_stats(self.party(id).active().calc_stats())
+    }
+
+    /// Previews the damage the move in a move slot of the active dragon
+    /// of a party would deal to its opponent. Returns None if the move
+    /// does not deal damage through the damage formula, or if it would
+    /// be stopped.
+    pub fn preview_damage(&self, id: PartyId, move_index: usize) -> Option<DamagePreview> {
+        let attacker = self.party(id).active();
+        let (move_stats, flags) = attacker.moves().get(move_index)?.attack().move_stats()?;
+        damage::preview_damage(
+            self.damage_calculator(),
+            &self.crit_rules,
+            attacker,
+            self.party(id.opposing()).active(),
+            self.side(id.opposing()),
+            move_stats,
+            flags,
+        )
+    }
+
+    fn move_context(&mut self, id: PartyId) -> MoveContext<'_> {
+        let (party, opposing, side, opposing_side) = match id {
+            PartyId::Party1 => (
+                &mut self.parties.0,
+                &mut self.parties.1,
+                &self.sides.0,
+                &self.sides.1,
+            ),
+            PartyId::Party2 => (
+                &mut self.parties.1,
+                &mut self.parties.0,
+                &self.sides.1,
+                &self.sides.0,
+            ),
+        };
+        MoveContext {
+            user: party.active_mut(),
+            opponent: opposing.active_mut(),
+            user_side: side,
+            opponent_side: opposing_side,
+            rng: &mut self.rng,
+            crit_rules: &self.crit_rules,
+            damage_calculator: self.damage_calculator.as_ref(),
+            actions: vec![],
         }
     }
 
-    pub fn attack(&mut self, party_id: PartyId, attack: &dyn MoveTrait<T>) {
-        self.messenger.on_attack(&self, party_id, attack.get_name());
+    /// Gets the active dragon of a party and of its opponent,
+    /// together with the random number generator of the battle.
+    fn active_and_rng_mut(&mut self, id: PartyId) -> (&mut PartyItem, &mut PartyItem, &mut StdRng) {
+        let (party, opposing) = match id {
+            PartyId::Party1 => (&mut self.parties.0, &mut self.parties.1),
+            PartyId::Party2 => (&mut self.parties.1, &mut self.parties.0),
+        };
+        (party.active_mut(), opposing.active_mut(), &mut self.rng)
+    }
+
+    pub fn attack(&mut self, party_id: PartyId, attack: &dyn MoveTrait) {
+        let move_name = attack.get_name();
+        let may_move = self.run_hooks(party_id, |e, ctx| e.before_move(ctx, move_name));
+        if !may_move {
+            // A move being charged is lost when the dragon can not strike.
+            self.party_mut(party_id)
+                .active_mut()
+                .remove_effect("charging");
+            return;
+        }
+
+        self.messenger
+            .on_attack(self, party_id, self.identity(party_id), move_name);
+        let opposing_id = party_id.opposing();
         let attack_result;
         let user_apply_result;
+        let actions;
         {
-            let (party, opposing, messenger) = self.party_opposing_messenger_mut(party_id);
-            attack_result =
-                attack.attack_opponent(opposing.active_mut(), party.active(), messenger);
-            user_apply_result =
-                attack.apply_to_user(party.active_mut(), opposing.active(), messenger);
+            let mut ctx = self.move_context(party_id);
+            attack_result = attack.attack_opponent(&mut ctx);
+            user_apply_result = attack.apply_to_user(&mut ctx);
+            actions = ctx.actions;
         }
-        if let MoveResult::Succeeded(Some(event)) = attack_result {
-            send_move_event_to_messenger(&self.messenger, event, party_id, &self);
+        let hit = matches!(attack_result, MoveResult::Hit(_));
+        let succeeded = hit || matches!(attack_result, MoveResult::Succeeded(_));
+        self.party_mut(party_id)
+            .active_mut()
+            .record_move(move_name, succeeded);
+        match attack_result {
+            MoveResult::Hit(events) | MoveResult::Succeeded(events) => {
+                let damage = events
+                    .iter()
+                    .map(|event| match event {
+                        MoveEvent::Damaged {
+                            rel_party_id: RelativePartyId::Opposing,
+                            damage_amount,
+                            ..
+                        } => *damage_amount,
+                        _ => 0,
+                    })
+                    .sum::<u32>();
+                // Recoil and redirected hits are dealt by the move itself.
+                // Damage dealt later by the hooks faints the user on its own.
+                let knocked_out_by_recoil = self.party(party_id).active().hp() == 0;
+                for event in events {
+                    send_move_event_to_messenger(&self.messenger, event, party_id, self);
+                }
+                self.run_effect_actions(party_id, actions);
+                if hit {
+                    self.run_hooks(opposing_id, |e, ctx| {
+                        e.on_hit(ctx, move_name);
+                        true
+                    });
+                }
+                if damage > 0 {
+                    self.run_hooks(opposing_id, |e, ctx| {
+                        e.on_damage(ctx, damage);
+                        true
+                    });
+                    if self.party(opposing_id).active().hp() == 0 {
+                        self.faint(opposing_id);
+                    }
+                }
+                if knocked_out_by_recoil {
+                    self.faint(party_id);
+                }
+            }
+            result => {
+                if let MoveResult::Missed = result {
+                    self.messenger
+                        .on_miss(self, party_id, self.identity(party_id));
+                }
+                // Only the messages of a failed move are kept.
+                let events = actions
+                    .into_iter()
+                    .filter(|a| matches!(a, EffectAction::Event(_)))
+                    .collect();
+                self.run_effect_actions(party_id, events);
+            }
         }
         if let Some(event) = user_apply_result {
-            send_move_event_to_messenger(&self.messenger, event, party_id, &self);
+            send_move_event_to_messenger(&self.messenger, event, party_id, self);
+        }
+        self.run_hooks(party_id, |e, ctx| {
+            e.after_move(ctx, move_name);
+            true
+        });
+    }
+
+    /// Switches the active dragon of a party, then applies the side
+    /// effects of the party to the dragon switching in, and lets it
+    /// enter the battle. Fails if the party can not switch to the
+    /// member, see [`Party::check_switch`].
+    pub fn switch(&mut self, party_id: PartyId, next: usize) -> Result<()> {
+        let original = self.party(party_id).active;
+        self.party_mut(party_id).switch(next)?;
+        let participants = self.participants_mut(party_id);
+        if !participants.contains(&next) {
+            participants.push(next);
+        }
+        *self.participants_mut(party_id.opposing()) =
+            vec![self.party(party_id.opposing()).active_index()];
+        self.party_mut(party_id)
+            .member_mut(original)
+            .unwrap()
+            .remove_category(EffectCategory::Volatile);
+        self.messenger.on_switch(
+            self,
+            party_id,
+            self.identity(party_id),
+            original as u8,
+            next as u8,
+        );
+
+        let damage = self.side(party_id).switch_in(self.party(party_id).active());
+        if damage > 0 {
+            self.deal_damage(party_id, damage);
+        }
+        if self.party(party_id).active().hp() > 0 {
+            self.enter_battle(party_id);
+        }
+        Ok(())
+    }
+
+    /// Attaches the ability of the active dragon of a party, then
+    /// calls the switch-in hooks of its effects.
+    fn enter_battle(&mut self, party_id: PartyId) {
+        let (item, opposing, rng) = self.active_and_rng_mut(party_id);
+        let actions = item.enter_battle(opposing, rng);
+        self.run_effect_actions(party_id, actions);
+        self.run_hooks(party_id, |e, ctx| {
+            e.on_switch_in(ctx);
+            true
+        });
+    }
+
+    /// Returns the parties ordered by the speed of their active dragons,
+    /// fastest first. On a speed tie, `Party1` goes first.
+    pub fn speed_order(&self) -> [PartyId; 2] {
+        if self.calc_stats(PartyId::Party2).speed > self.calc_stats(PartyId::Party1).speed {
+            [PartyId::Party2, PartyId::Party1]
+        } else {
+            [PartyId::Party1, PartyId::Party2]
         }
     }
 
+    /// Ends the turn. The end of turn hooks of both active dragons are
+    /// called by descending effect priority; on equal priority the
+    /// effects of the faster dragon go first. Then every effect is
+    /// notified about the new turn.
     pub fn turn(&mut self) {
-        self.parties.0.active_mut().turn();
-        self.parties.1.active_mut().turn();
+        let mut priorities: Vec<i8> = [PartyId::Party1, PartyId::Party2]
+            .iter()
+            .flat_map(|&id| self.party(id).active().effects.iter())
+            .map(|(_, e)| e.priority())
+            .collect();
+        priorities.sort_unstable_by(|a, b| b.cmp(a));
+        priorities.dedup();
+        let order = self.speed_order();
+        for priority in priorities {
+            for party_id in order {
+                if self.party(party_id).active().hp() > 0 {
+                    self.run_hooks(party_id, |e, ctx| {
+                        if e.priority() == priority {
+                            e.end_of_turn(ctx);
+                        }
+                        true
+                    });
+                }
+            }
+        }
+        for party_id in [PartyId::Party1, PartyId::Party2] {
+            let (item, opposing, rng) = self.active_and_rng_mut(party_id);
+            let actions = item.turn(opposing, rng);
+            self.run_effect_actions(party_id, actions);
+        }
+        self.sides.0.turn();
+        self.sides.1.turn();
+    }
+
+    /// Runs an effect hook on the active dragon of a party, then
+    /// carries out the actions requested by the effects. Returns
+    /// false if one of the hooks did.
+    fn run_hooks<F>(&mut self, party_id: PartyId, hook: F) -> bool
+    where
+        F: FnMut(&mut dyn LongTermEffectTrait, &mut EffectContext) -> bool,
+    {
+        let (item, opposing, rng) = self.active_and_rng_mut(party_id);
+        let (passed, actions) = item.run_hooks(opposing, rng, hook);
+        self.run_effect_actions(party_id, actions);
+        passed
+    }
+
+    fn run_effect_actions(&mut self, owner: PartyId, actions: Vec<EffectAction>) {
+        for action in actions {
+            match action {
+                EffectAction::Damage(target, amount) => {
+                    self.deal_damage(owner.relative(target), amount);
+                }
+                EffectAction::Heal(target, amount) => {
+                    let target = owner.relative(target);
+                    let healed = self.party_mut(target).active_mut().heal(amount);
+                    self.messenger
+                        .on_heal(self, target, self.identity(target), healed);
+                }
+                EffectAction::AddEffect(target, effect) => {
+                    let target = owner.relative(target);
+                    let (item, opposing, rng) = self.active_and_rng_mut(target);
+                    let actions = item.attach(effect, Some(opposing), rng);
+                    self.run_effect_actions(target, actions);
+                }
+                EffectAction::Event(event) => {
+                    send_move_event_to_messenger(&self.messenger, event, owner, self);
+                }
+                EffectAction::RemoveItem(target) => {
+                    self.party_mut(owner.relative(target))
+                        .active_mut()
+                        .take_item();
+                }
+                EffectAction::AddSideEffect(target, effect) => {
+                    self.side_mut(owner.relative(target)).add_effect(effect);
+                }
+                EffectAction::SetWeather(weather) => self.set_weather(weather),
+            }
+        }
+    }
+
+    /// Moves the held item of the opposing active dragon to the active
+    /// dragon of `thief`. Fails if the thief already holds an item or
+    /// the opponent holds none.
+    pub fn steal_item(&mut self, thief: PartyId) -> bool {
+        let (party, opposing) = self.party_and_opposing_mut(thief);
+        if party.active().item().is_some() {
+            return false;
+        }
+        let item = match opposing.active_mut().take_item() {
+            None => return false,
+            Some(item) => item,
+        };
+        let message = format!("stole {}", item.name);
+        party.active_mut().give_item(item);
+        self.messenger
+            .on_message(self, thief, self.identity(thief), &message);
+        true
+    }
+
+    /// Swaps the held items of the two active dragons.
+    pub fn swap_items(&mut self) {
+        let first = self.parties.0.active_mut().take_item();
+        let second = self.parties.1.active_mut().take_item();
+        if let Some(item) = second {
+            self.parties.0.active_mut().give_item(item);
+        }
+        if let Some(item) = first {
+            self.parties.1.active_mut().give_item(item);
+        }
+        for party_id in [PartyId::Party1, PartyId::Party2] {
+            if let Some(item) = self.party(party_id).active().item() {
+                let message = format!("obtained {}", item.name);
+                self.messenger
+                    .on_message(self, party_id, self.identity(party_id), &message);
+            }
+        }
+    }
+
+    /// Damages the active dragon of a party outside of a move,
+    /// and reports it to the messenger.
+    fn deal_damage(&mut self, party_id: PartyId, amount: u32) {
+        let item = self.party_mut(party_id).active_mut();
+        let was_alive = item.hp() > 0;
+        item.damage(amount);
+        self.messenger
+            .on_damage(self, party_id, self.identity(party_id), amount);
+        if was_alive && self.party(party_id).active().hp() == 0 {
+            self.faint(party_id);
+        }
+    }
+
+    /// Notifies the effects of both active dragons that the active
+    /// dragon of `party_id` has fainted, then removes the volatile
+    /// conditions of the fainted dragon.
+    fn faint(&mut self, party_id: PartyId) {
+        self.run_hooks(party_id, |e, ctx| {
+            e.on_faint(ctx, RelativePartyId::User);
+            true
+        });
+        self.party_mut(party_id)
+            .active_mut()
+            .remove_category(EffectCategory::Volatile);
+        self.run_hooks(party_id.opposing(), |e, ctx| {
+            e.on_faint(ctx, RelativePartyId::Opposing);
+            true
+        });
+        if self.experience_gain {
+            self.share_experience(party_id);
+        }
+    }
+
+    /// Shares the experience yielded by the fainted active dragon of
+    /// `party_id` between the participants of the opposing party which
+    /// have not fainted.
+    fn share_experience(&mut self, party_id: PartyId) {
+        let fainted = &self.party(party_id).active().dragon;
+        let experience = match fainted.species() {
+            Some(species) => species.experience_yield * fainted.level() as u32 / 7,
+            None => 0,
+        };
+        if experience == 0 {
+            return;
+        }
+        let winner = party_id.opposing();
+        let participants: Vec<usize> = match winner {
+            PartyId::Party1 => &self.participants.0,
+            PartyId::Party2 => &self.participants.1,
+        }
+        .iter()
+        .copied()
+        .filter(|&index| self.party(winner).member(index).unwrap().hp() > 0)
+        .collect();
+        if participants.is_empty() {
+            return;
+        }
+        let share = (experience / participants.len() as u32).max(1);
+        for index in participants {
+            let member = self.party_mut(winner).member_mut(index).unwrap();
+            let events = member.dragon.gain_experience(share);
+            let dragon = self.party(winner).member(index).unwrap().dragon.identity();
+            for event in &events {
+                self.messenger
+                    .on_progress(self, winner, dragon, index as u8, event);
+            }
+        }
     }
 }
 
@@ -80,57 +855,357 @@ fn send_move_event_to_messenger<T: Messenger>(
     user_party_id: PartyId,
     battlefield: &Battlefield<T>,
 ) {
+    let party = |rel_party_id| user_party_id.relative(rel_party_id);
+    let dragon = |rel_party_id| battlefield.identity(party(rel_party_id));
     match move_event {
+        MoveEvent::Calculated {
+            rel_party_id,
+            breakdown,
+        } => {
+            messenger.on_damage_calculated(battlefield, party(rel_party_id), &breakdown);
+        }
         MoveEvent::Damaged {
             rel_party_id,
             damage_amount,
+            critical,
         } => {
+            if critical {
+                messenger.on_critical_hit(battlefield, party(rel_party_id), dragon(rel_party_id));
+            }
             messenger.on_damage(
                 battlefield,
-                user_party_id.relative(rel_party_id),
+                party(rel_party_id),
+                dragon(rel_party_id),
                 damage_amount,
             );
         }
+        MoveEvent::Healed {
+            rel_party_id,
+            heal_amount,
+        } => {
+            messenger.on_heal(
+                battlefield,
+                party(rel_party_id),
+                dragon(rel_party_id),
+                heal_amount,
+            );
+        }
         MoveEvent::Effected {
             rel_party_id,
             description,
         } => {
             messenger.on_effect_applied(
                 battlefield,
-                user_party_id.relative(rel_party_id),
+                party(rel_party_id),
+                dragon(rel_party_id),
                 &description,
             );
         }
+        MoveEvent::ItemActivated {
+            rel_party_id,
+            item_name,
+        } => {
+            messenger.on_item_activated(
+                battlefield,
+                party(rel_party_id),
+                dragon(rel_party_id),
+                &item_name,
+            );
+        }
+        MoveEvent::Message {
+            rel_party_id,
+            message,
+        } => {
+            messenger.on_message(
+                battlefield,
+                party(rel_party_id),
+                dragon(rel_party_id),
+                &message,
+            );
+        }
     }
 }
 
+/// Reports what happens in a battle. Every callback concerning a party
+/// also gets the identity of its active dragon, except for `on_bag_item`
+/// and `on_progress`, which get the identity of the member concerned.
 pub trait Messenger: Send + Sync + Sized {
-    fn on_attack(&self, field: &Battlefield<Self>, party: PartyId, move_name: &str);
-    fn on_damage(&self, field: &Battlefield<Self>, party: PartyId, amount: u32);
-    fn on_switch(&self, field: &Battlefield<Self>, party: PartyId, original: u8, switched: u8);
-    fn on_effect_applied(&self, field: &Battlefield<Self>, party: PartyId, effect_desc: &str);
+    fn on_attack(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        move_name: &str,
+    );
+    fn on_damage_calculated(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        breakdown: &DamageBreakdown,
+    );
+    fn on_damage(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        amount: u32,
+    );
+    fn on_critical_hit(&self, field: &Battlefield<Self>, party: PartyId, dragon: &DragonIdentity);
+    fn on_miss(&self, field: &Battlefield<Self>, party: PartyId, dragon: &DragonIdentity);
+    fn on_heal(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        amount: u32,
+    );
+    fn on_switch(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        original: u8,
+        switched: u8,
+    );
+    fn on_effect_applied(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        effect_desc: &str,
+    );
+    fn on_item_activated(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        item_name: &str,
+    );
+    fn on_bag_item(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        item_name: &str,
+        target: u8,
+    );
+    fn on_message(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        message: &str,
+    );
+    fn on_progress(
+        &self,
+        field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        member: u8,
+        event: &ProgressEvent,
+    );
 }
 
 pub struct NopMessenger;
 impl Messenger for NopMessenger {
-    fn on_attack(&self, _field: &Battlefield<Self>, _party: PartyId, _move_name: &str) {}
-    fn on_damage(&self, _field: &Battlefield<Self>, _party: PartyId, _amount: u32) {}
-    fn on_switch(&self, _field: &Battlefield<Self>, _party: PartyId, _origin: u8, _next: u8) {}
-    fn on_effect_applied(&self, _field: &Battlefield<Self>, _party: PartyId, _effect_desc: &str) {}
+    fn on_attack(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _move_name: &str,
+    ) {
+    }
+    fn on_damage_calculated(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _breakdown: &DamageBreakdown,
+    ) {
+    }
+    fn on_damage(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _amount: u32,
+    ) {
+    }
+    fn on_critical_hit(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+    ) {
+    }
+    fn on_miss(&self, _field: &Battlefield<Self>, _party: PartyId, _dragon: &DragonIdentity) {}
+    fn on_heal(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _amount: u32,
+    ) {
+    }
+    fn on_switch(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _origin: u8,
+        _next: u8,
+    ) {
+    }
+    fn on_effect_applied(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _effect_desc: &str,
+    ) {
+    }
+    fn on_item_activated(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _item_name: &str,
+    ) {
+    }
+    fn on_bag_item(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _name: &str,
+        _target: u8,
+    ) {
+    }
+    fn on_message(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _message: &str,
+    ) {
+    }
+    fn on_progress(
+        &self,
+        _field: &Battlefield<Self>,
+        _party: PartyId,
+        _dragon: &DragonIdentity,
+        _member: u8,
+        _event: &ProgressEvent,
+    ) {
+    }
 }
 
+#[cfg(test)]
 pub(crate) struct TestMessenger;
+#[cfg(test)]
 impl Messenger for TestMessenger {
-    fn on_attack(&self, _field: &Battlefield<Self>, party: PartyId, move_name: &str) {
-        println!("Party {} attacks with {}!", party, move_name);
+    fn on_attack(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        move_name: &str,
+    ) {
+        println!("{} ({}) used {}!", dragon.name(), party, move_name);
+    }
+    fn on_damage_calculated(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        breakdown: &DamageBreakdown,
+    ) {
+        println!("Damage against Party {}: {:?}", party, breakdown);
+    }
+    fn on_damage(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        amount: u32,
+    ) {
+        println!("{} ({}) damaged by {}!", dragon.name(), party, amount);
+    }
+    fn on_critical_hit(&self, _field: &Battlefield<Self>, party: PartyId, dragon: &DragonIdentity) {
+        println!("A critical hit on {} ({})!", dragon.name(), party);
+    }
+    fn on_miss(&self, _field: &Battlefield<Self>, party: PartyId, dragon: &DragonIdentity) {
+        println!("The attack of {} ({}) missed!", dragon.name(), party);
+    }
+    fn on_heal(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        amount: u32,
+    ) {
+        println!("{} ({}) healed by {}!", dragon.name(), party, amount);
+    }
+    fn on_switch(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        origin: u8,
+        next: u8,
+    ) {
+        println!(
+            "{}: switch {} to {}, go {}!",
+            party,
+            origin,
+            next,
+            dragon.name()
+        );
+    }
+    fn on_effect_applied(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        effect_desc: &str,
+    ) {
+        println!("{} ({}) got effect: {}!", dragon.name(), party, effect_desc);
+    }
+    fn on_item_activated(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        item_name: &str,
+    ) {
+        println!("{}'s ({}) {} activated!", dragon.name(), party, item_name);
     }
-    fn on_damage(&self, _field: &Battlefield<Self>, party: PartyId, amount: u32) {
-        println!("Party {} damaged by {}!", party, amount);
+    fn on_bag_item(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        item_name: &str,
+        _target: u8,
+    ) {
+        println!("{} used {} on {}!", party, item_name, dragon.name());
     }
-    fn on_switch(&self, _field: &Battlefield<Self>, party: PartyId, origin: u8, next: u8) {
-        println!("Party {}: switch {} to {}", party, origin, next);
+    fn on_message(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        message: &str,
+    ) {
+        println!("{} ({}) {}!", dragon.name(), party, message);
     }
-    fn on_effect_applied(&self, _field: &Battlefield<Self>, party: PartyId, effect_desc: &str) {
-        println!("Party {} got effect: {}!", party, effect_desc);
+    fn on_progress(
+        &self,
+        _field: &Battlefield<Self>,
+        party: PartyId,
+        dragon: &DragonIdentity,
+        _member: u8,
+        event: &ProgressEvent,
+    ) {
+        println!("{} ({}): {:?}", dragon.name(), party, event);
     }
 }
@@ -0,0 +1,299 @@
+use std::ops::RangeInclusive;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    moves::{AttackContext, AttackFlags, MoveStats},
+    party::PartyItem,
+    side::SideEffects,
+    types::TypeChart,
+};
+
+/// The classic damage formula, without any multipliers.
+pub fn base_damage(level: u8, attack: u32, defense: u32, base_power: u32) -> u32 {
+    (2 * level as u32 / 5 + 2) * attack * base_power / defense.max(1) / 50 + 2
+}
+
+/// Everything a damage calculation is based on. The attack is the
+/// one returned by [`PartyItem::offend`], before any critical hit
+/// adjustments.
+pub struct DamageInput<'a> {
+    pub attacker: &'a PartyItem,
+    pub defender: &'a PartyItem,
+    pub defender_side: &'a SideEffects,
+    pub attack: &'a AttackContext,
+    pub crit_rules: &'a CritRules,
+    pub critical: bool,
+    /// The random damage roll, as a percentage in the
+    /// [`roll_range`](DamageCalculator::roll_range) of the calculator.
+    pub roll: u32,
+}
+
+/// Every step of a damage calculation, so that it can be shown to players.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DamageBreakdown {
+    pub level: u8,
+    pub attack: u32,
+    pub defense: u32,
+    pub base_power: u32,
+    /// The damage of the formula before any multipliers.
+    pub base_damage: u32,
+    pub roll: u32,
+    pub stab: f32,
+    pub type_effectiveness: f32,
+    pub critical: bool,
+    pub crit_multiplier: f32,
+    /// The multiplier coming from the effects of both dragons.
+    pub modifier: f32,
+    /// The damage before the side effects of the defender are applied.
+    pub damage_before_side: u32,
+    /// The final damage.
+    pub damage: u32,
+}
+
+/// Calculates the damage of attacks. The battlefield uses the
+/// [`ClassicDamageCalculator`] unless another one is installed.
+pub trait DamageCalculator: Send + Sync {
+    /// The range of the random damage roll, in percent.
+    fn roll_range(&self) -> RangeInclusive<u32> {
+        85..=100
+    }
+
+    /// Calculates the damage of an attack. Must not have side effects.
+    fn calculate(&self, input: &DamageInput) -> DamageBreakdown;
+}
+
+/// The classic damage formula: the base damage from the level of the
+/// attacker, its attack, the defense of the defender and the power of
+/// the move is multiplied by the random roll, STAB, type effectiveness,
+/// the critical hit multiplier and the modifier of the effects. Finally
+/// the side effects of the defender are applied.
+pub struct ClassicDamageCalculator {
+    pub type_chart: TypeChart,
+    /// The multiplier for moves sharing a type with their user.
+    pub stab: f32,
+    /// The lowest random roll, in percent.
+    pub min_roll: u32,
+}
+
+impl ClassicDamageCalculator {
+    pub fn new(type_chart: TypeChart) -> Self {
+        Self {
+            type_chart,
+            stab: 1.5,
+            min_roll: 85,
+        }
+    }
+}
+
+impl Default for ClassicDamageCalculator {
+    fn default() -> Self {
+        Self::new(TypeChart::new())
+    }
+}
+
+impl DamageCalculator for ClassicDamageCalculator {
+    fn roll_range(&self) -> RangeInclusive<u32> {
+        self.min_roll..=100
+    }
+
+    fn calculate(&self, input: &DamageInput) -> DamageBreakdown {
+        let rules = input.crit_rules;
+        let attack = if input.critical {
+            rules.adjust_stages(input.attack.clone())
+        } else {
+            input.attack.clone()
+        };
+        let level = input.attacker.dragon.level();
+        let attack_stat = input
+            .attacker
+            .calc_stats_with(attack.attacker_stages)
+            .attack;
+        let defense_stat = input
+            .defender
+            .calc_stats_with(attack.defender_stages)
+            .defense;
+        let base_power = attack.move_stats.base_power;
+        let base = base_damage(level, attack_stat, defense_stat, base_power);
+
+        let move_type = attack.move_stats.move_type.as_deref();
+        let stab = match move_type {
+            Some(t) if input.attacker.dragon.has_type(t) => self.stab,
+            _ => 1.,
+        };
+        let type_effectiveness = match move_type {
+            Some(t) => self.type_chart.against(t, input.defender.dragon.types()),
+            None => 1.,
+        };
+        let crit_multiplier = if input.critical { rules.multiplier } else { 1. };
+
+        let mut damage = base * input.roll / 100;
+        for multiplier in [stab, type_effectiveness, crit_multiplier, attack.modifier] {
+            damage = (damage as f32 * multiplier) as u32;
+        }
+        let damage_before_side = damage;
+        damage = input.defender_side.defend(
+            damage,
+            &attack.move_stats,
+            input.critical && rules.ignore_screens,
+        );
+
+        DamageBreakdown {
+            level,
+            attack: attack_stat,
+            defense: defense_stat,
+            base_power,
+            base_damage: base,
+            roll: input.roll,
+            stab,
+            type_effectiveness,
+            critical: input.critical,
+            crit_multiplier,
+            modifier: attack.modifier,
+            damage_before_side,
+            damage,
+        }
+    }
+}
+
+/// The possible damage of an attack over every random roll.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DamageRange {
+    pub min: u32,
+    pub max: u32,
+    /// `min` as a percentage of the maximum HP of the defender.
+    pub min_percent: f32,
+    /// `max` as a percentage of the maximum HP of the defender.
+    pub max_percent: f32,
+    /// The chance that one hit knocks out the defender from its current HP.
+    pub ko_chance: f64,
+    /// The chance that two hits knock out the defender from its current HP.
+    pub two_hit_ko_chance: f64,
+}
+
+impl DamageRange {
+    fn new(damages: &[u32], defender: &PartyItem) -> Self {
+        let (hp, max_hp) = (defender.hp(), defender.dragon.max_hp());
+        let min = damages.iter().copied().min().unwrap_or(0);
+        let max = damages.iter().copied().max().unwrap_or(0);
+        let count = damages.len().max(1) as f64;
+        let kos = damages.iter().filter(|&&d| d >= hp).count();
+        let two_hit_kos = damages
+            .iter()
+            .map(|a| damages.iter().filter(|&&b| a + b >= hp).count())
+            .sum::<usize>();
+        Self {
+            min,
+            max,
+            min_percent: min as f32 * 100. / max_hp as f32,
+            max_percent: max as f32 * 100. / max_hp as f32,
+            ko_chance: kos as f64 / count,
+            two_hit_ko_chance: two_hit_kos as f64 / (count * count),
+        }
+    }
+}
+
+/// What an attack would do, with and without a critical hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DamagePreview {
+    pub normal: DamageRange,
+    pub critical: DamageRange,
+    /// The chance of the attack being a critical hit.
+    pub crit_chance: f64,
+}
+
+/// Previews the damage of an attack without changing anything or
+/// consuming randomness. Returns None if the effects of the dragons
+/// would stop the attack.
+pub fn preview_damage(
+    calculator: &dyn DamageCalculator,
+    crit_rules: &CritRules,
+    attacker: &PartyItem,
+    defender: &PartyItem,
+    defender_side: &SideEffects,
+    move_stats: MoveStats,
+    flags: AttackFlags,
+) -> Option<DamagePreview> {
+    let attack = attacker.offend(move_stats, flags, defender)?;
+    let damages = |critical| {
+        calculator
+            .roll_range()
+            .map(|roll| {
+                calculator
+                    .calculate(&DamageInput {
+                        attacker,
+                        defender,
+                        defender_side,
+                        attack: &attack,
+                        crit_rules,
+                        critical,
+                        roll,
+                    })
+                    .damage
+            })
+            .collect::<Vec<_>>()
+    };
+    Some(DamagePreview {
+        normal: DamageRange::new(&damages(false), defender),
+        critical: DamageRange::new(&damages(true), defender),
+        crit_chance: crit_rules.chance(attack.move_stats.crit_calc),
+    })
+}
+
+/// The rules deciding how often critical hits happen and what they do.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CritRules {
+    /// The chance of a critical hit at crit stage `n` is
+    /// `1 / stage_table[n]`. Stages past the end of the table use
+    /// its last entry.
+    pub stage_table: Vec<u32>,
+    /// The damage of a critical hit is multiplied by this.
+    pub multiplier: f32,
+    /// Critical hits ignore the negative attack stages of the attacker.
+    pub ignore_attacker_drops: bool,
+    /// Critical hits ignore the positive defense stages of the defender.
+    pub ignore_defender_boosts: bool,
+    /// Critical hits ignore the damage reduction of screens. Other
+    /// side effects, such as weather, still apply.
+    pub ignore_screens: bool,
+}
+
+impl CritRules {
+    /// Returns the chance of a critical hit at a crit stage.
+    pub fn chance(&self, stage: u8) -> f64 {
+        let index = (stage as usize).min(self.stage_table.len().saturating_sub(1));
+        match self.stage_table.get(index) {
+            Some(&denominator) if denominator > 0 => 1. / denominator as f64,
+            _ => 0.,
+        }
+    }
+
+    /// Decides whether an attack at the given crit stage is a critical hit.
+    pub fn roll(&self, stage: u8, rng: &mut dyn RngCore) -> bool {
+        rng.gen_bool(self.chance(stage))
+    }
+
+    /// Removes the stat stages a critical hit ignores from an attack.
+    pub fn adjust_stages(&self, mut attack: AttackContext) -> AttackContext {
+        if self.ignore_attacker_drops {
+            attack.attacker_stages.attack = attack.attacker_stages.attack.max(0);
+        }
+        if self.ignore_defender_boosts {
+            attack.defender_stages.defense = attack.defender_stages.defense.min(0);
+        }
+        attack
+    }
+}
+
+impl Default for CritRules {
+    fn default() -> Self {
+        Self {
+            stage_table: vec![24, 8, 2, 1],
+            multiplier: 1.5,
+            ignore_attacker_drops: true,
+            ignore_defender_boosts: true,
+            ignore_screens: true,
+        }
+    }
+}
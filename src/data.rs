@@ -1,2 +1,141 @@
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use crate::{
+    ability::AbilityData,
+    dragon::{DragonData, Evolution, EvolutionCondition, EvolutionTrigger, StatOrder},
+    item::ItemData,
+    moves::MoveTrait,
+    party::PartyItem,
+};
+
 pub mod dragons;
 pub mod moves;
+
+/// A move as it is taught to dragons.
+#[derive(Clone)]
+pub struct MoveEntry {
+    pub attack: Arc<dyn MoveTrait>,
+    pub max_pp: u8,
+}
+
+/// A check deciding whether a dragon may evolve, see [`EvolutionCondition::Custom`].
+pub type EvolutionCheck = Box<dyn Fn(&PartyItem) -> bool + Send + Sync>;
+
+/// The species, moves, held items and abilities loaded from data,
+/// looked up by their names.
+#[derive(Default)]
+pub struct Dex {
+    species: HashMap<String, Arc<DragonData>>,
+    moves: HashMap<String, MoveEntry>,
+    items: HashMap<String, ItemData>,
+    abilities: HashMap<String, AbilityData>,
+    evolution_checks: HashMap<String, EvolutionCheck>,
+}
+
+impl Dex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_species(&mut self, species: DragonData) {
+        self.species.insert(species.name.clone(), Arc::new(species));
+    }
+
+    pub fn add_move(&mut self, attack: Arc<dyn MoveTrait>, max_pp: u8) {
+        self.moves
+            .insert(attack.get_name().to_string(), MoveEntry { attack, max_pp });
+    }
+
+    pub fn add_item(&mut self, item: ItemData) {
+        self.items.insert(item.name.clone(), item);
+    }
+
+    pub fn add_ability(&mut self, ability: AbilityData) {
+        self.abilities.insert(ability.name.clone(), ability);
+    }
+
+    pub fn species(&self, name: &str) -> Option<&Arc<DragonData>> {
+        self.species.get(name)
+    }
+
+    pub fn get_move(&self, name: &str) -> Option<&MoveEntry> {
+        self.moves.get(name)
+    }
+
+    pub fn item(&self, name: &str) -> Option<&ItemData> {
+        self.items.get(name)
+    }
+
+    pub fn ability(&self, name: &str) -> Option<&AbilityData> {
+        self.abilities.get(name)
+    }
+
+    /// Registers a check for [`EvolutionCondition::Custom`] evolutions.
+    pub fn add_evolution_check(&mut self, name: &str, check: EvolutionCheck) {
+        self.evolution_checks.insert(name.to_string(), check);
+    }
+
+    /// Returns the first evolution of the dragon whose condition is met
+    /// by the trigger, if the species it evolves into is known.
+    pub fn evolution<'a>(
+        &self,
+        item: &'a PartyItem,
+        trigger: EvolutionTrigger,
+    ) -> Option<&'a Evolution> {
+        let dragon = &item.dragon;
+        let level_up = |level| trigger == EvolutionTrigger::LevelUp && dragon.level() >= level;
+        dragon
+            .species()?
+            .evolutions
+            .iter()
+            .filter(|e| self.species.contains_key(&e.into))
+            .find(|e| match &e.condition {
+                EvolutionCondition::Level(level) => level_up(*level),
+                EvolutionCondition::UseItem(name) => trigger == EvolutionTrigger::UseItem(name),
+                EvolutionCondition::Trade { held_item } => {
+                    trigger == EvolutionTrigger::Trade
+                        && held_item
+                            .iter()
+                            .all(|held| item.item().map(|i| &i.name) == Some(held))
+                }
+                EvolutionCondition::StatComparison {
+                    level,
+                    first,
+                    second,
+                    order,
+                } => {
+                    let stats = dragon.stats();
+                    let expected = match order {
+                        StatOrder::Greater => Ordering::Greater,
+                        StatOrder::Equal => Ordering::Equal,
+                        StatOrder::Less => Ordering::Less,
+                    };
+                    level_up(*level) && stats.get(*first).cmp(&stats.get(*second)) == expected
+                }
+                EvolutionCondition::Custom(name) => {
+                    trigger == EvolutionTrigger::LevelUp
+                        && self
+                            .evolution_checks
+                            .get(name)
+                            .is_some_and(|check| check(item))
+                }
+            })
+    }
+
+    /// Evolves the dragon if the trigger meets the condition of one of
+    /// its evolutions, using up the held item a trade asks for. Returns
+    /// the species the dragon evolved into.
+    pub fn evolve(
+        &self,
+        item: &mut PartyItem,
+        trigger: EvolutionTrigger,
+    ) -> Option<Arc<DragonData>> {
+        let evolution = self.evolution(item, trigger)?.clone();
+        if let EvolutionCondition::Trade { held_item: Some(_) } = evolution.condition {
+            item.take_item();
+        }
+        let species = self.species[&evolution.into].clone();
+        item.dragon.evolve(species.clone());
+        Some(species)
+    }
+}
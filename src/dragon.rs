@@ -2,11 +2,128 @@ use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::ops::Add;
 use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::events::ProgressEvent;
 
 #[derive(Serialize, Deserialize)]
 pub struct DragonData {
     pub name: String,
     pub base_stats: Stats,
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// The names of the abilities a dragon of this species may have.
+    #[serde(default)]
+    pub abilities: Vec<String>,
+    /// The moves a dragon of this species can learn.
+    #[serde(default)]
+    pub learnset: Vec<LearnsetEntry>,
+    #[serde(default)]
+    pub growth_rate: GrowthRate,
+    /// The experience a dragon of this species yields when it faints
+    /// is based on this.
+    #[serde(default)]
+    pub experience_yield: u32,
+    /// The species a dragon of this species can evolve into.
+    #[serde(default)]
+    pub evolutions: Vec<Evolution>,
+}
+
+/// A species a dragon can evolve into, and what it takes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Evolution {
+    pub into: String,
+    pub condition: EvolutionCondition,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EvolutionCondition {
+    /// The dragon levels up to at least this level.
+    Level(u8),
+    /// A bag item is used on the dragon.
+    UseItem(String),
+    /// The dragon is traded, holding the item if one is given. The
+    /// held item is used up.
+    Trade { held_item: Option<String> },
+    /// The dragon levels up to at least `level`, and `first` compares
+    /// to `second` as given. Only attack, defense and speed can be compared.
+    StatComparison {
+        level: u8,
+        first: Stat,
+        second: Stat,
+        order: StatOrder,
+    },
+    /// The dragon levels up, and the check registered under the name
+    /// in the [`Dex`](crate::data::Dex) passes.
+    Custom(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatOrder {
+    Greater,
+    Equal,
+    Less,
+}
+
+/// What happened to a dragon which might let it evolve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvolutionTrigger<'a> {
+    LevelUp,
+    UseItem(&'a str),
+    Trade,
+}
+
+/// The highest level a dragon can reach.
+pub const MAX_LEVEL: u8 = 100;
+
+/// The highest individual value of a stat, see [`BattleDragon::with_values`].
+pub const MAX_VALUE: u32 = 31;
+
+/// The highest stat stage, and the negated lowest one.
+pub const MAX_STAGE: i8 = 6;
+
+/// Adds two stages, keeping the result within [`MAX_STAGE`].
+fn add_stages(stage: i8, amount: i8) -> i8 {
+    stage.saturating_add(amount).clamp(-MAX_STAGE, MAX_STAGE)
+}
+
+/// How much experience a species needs to reach each level.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GrowthRate {
+    Fast,
+    #[default]
+    MediumFast,
+    MediumSlow,
+    Slow,
+    /// The total experience needed for each level, starting at level 2.
+    /// Levels past the end of the table can not be reached.
+    Table(Vec<u32>),
+}
+
+impl GrowthRate {
+    /// The total experience a dragon needs to reach `level`, or None if
+    /// the level can not be reached.
+    pub fn experience_for(&self, level: u8) -> Option<u32> {
+        if level <= 1 {
+            return Some(0);
+        }
+        let n = level as i64;
+        let experience = match self {
+            GrowthRate::Fast => 4 * n * n * n / 5,
+            GrowthRate::MediumFast => n * n * n,
+            GrowthRate::MediumSlow => 6 * n * n * n / 5 - 15 * n * n + 100 * n - 140,
+            GrowthRate::Slow => 5 * n * n * n / 4,
+            GrowthRate::Table(table) => return table.get(level as usize - 2).copied(),
+        };
+        Some(experience.max(0) as u32)
+    }
+}
+
+/// A move a species learns once it reaches a level.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LearnsetEntry {
+    pub level: u8,
+    pub move_name: String,
 }
 
 impl DragonData {
@@ -14,15 +131,70 @@ impl DragonData {
         Self {
             name: name.to_string(),
             base_stats,
+            types: vec![],
+            abilities: vec![],
+            learnset: vec![],
+            growth_rate: GrowthRate::default(),
+            experience_yield: 0,
+            evolutions: vec![],
         }
     }
+
+    pub fn with_evolution(mut self, into: &str, condition: EvolutionCondition) -> Self {
+        self.evolutions.push(Evolution {
+            into: into.to_string(),
+            condition,
+        });
+        self
+    }
+
+    pub fn with_growth(mut self, growth_rate: GrowthRate, experience_yield: u32) -> Self {
+        self.growth_rate = growth_rate;
+        self.experience_yield = experience_yield;
+        self
+    }
+
+    pub fn with_types(mut self, types: &[&str]) -> Self {
+        self.types = types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn with_abilities(mut self, abilities: &[&str]) -> Self {
+        self.abilities = abilities.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    /// Sets the moves the species learns, as `(level, move name)` pairs.
+    pub fn with_learnset(mut self, learnset: &[(u8, &str)]) -> Self {
+        self.learnset = learnset
+            .iter()
+            .map(|&(level, move_name)| LearnsetEntry {
+                level,
+                move_name: move_name.to_string(),
+            })
+            .collect();
+        self
+    }
+
+    /// Returns true if a dragon of this species at `level` may know the move.
+    pub fn can_learn(&self, move_name: &str, level: u8) -> bool {
+        self.learnset
+            .iter()
+            .any(|e| e.move_name == move_name && e.level <= level)
+    }
+
+    /// Returns true if a dragon of this species may have the ability.
+    pub fn can_have_ability(&self, ability: &str) -> bool {
+        self.abilities.iter().any(|a| a == ability)
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Stats {
     pub attack: u32,
     pub defense: u32,
     pub hp: u32,
+    pub speed: u32,
 }
 
 fn apply_stat_stage(base_value: u32, stage: i8) -> u32 {
@@ -35,14 +207,27 @@ impl Stats {
             attack: 100,
             defense: 100,
             hp: 100,
+            speed: 100,
         }
     }
 
-    pub fn new_exact(attack: u32, defense: u32, hp: u32) -> Self {
+    pub fn new_exact(attack: u32, defense: u32, hp: u32, speed: u32) -> Self {
         Self {
             attack,
             defense,
             hp,
+            speed,
+        }
+    }
+
+    /// Gets a stat. Accuracy and evasion are not stats of their own,
+    /// so they are zero.
+    pub fn get(&self, stat: Stat) -> u32 {
+        match stat {
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::Speed => self.speed,
+            Stat::Accuracy | Stat::Evasion => 0,
         }
     }
 
@@ -51,11 +236,36 @@ impl Stats {
             attack: apply_stat_stage(self.attack, stages.attack),
             defense: apply_stat_stage(self.defense, stages.defense),
             hp: self.hp,
+            speed: apply_stat_stage(self.speed, stages.speed),
+        }
+    }
+
+    /// Stats where every stat is zero.
+    pub fn zero() -> Self {
+        Self::new_exact(0, 0, 0, 0)
+    }
+
+    /// The stats of a dragon at `level`, from the base stats of its
+    /// species and its individual values. Saturates instead of
+    /// overflowing on absurd inputs.
+    pub fn at_level(base: Stats, values: Stats, level: u8) -> Self {
+        let scale = |base: u32, value: u32| {
+            base.saturating_mul(2)
+                .saturating_add(value)
+                .saturating_mul(level as u32)
+                / 100
+        };
+        Self {
+            attack: scale(base.attack, values.attack).saturating_add(5),
+            defense: scale(base.defense, values.defense).saturating_add(5),
+            // `calculate_hp` adds the other 5.
+            hp: scale(base.hp, values.hp).saturating_add(level as u32 + 5),
+            speed: scale(base.speed, values.speed).saturating_add(5),
         }
     }
 
     pub fn calculate_hp(&self) -> u32 {
-        self.hp + 5
+        self.hp.saturating_add(5)
     }
 }
 
@@ -72,10 +282,21 @@ impl Mul<StatStages> for Stats {
     }
 }
 
+/// A stat which has a stage.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    Attack,
+    Defense,
+    Speed,
+    Accuracy,
+    Evasion,
+}
+
 #[derive(Clone, Copy)]
 pub struct StatStages {
     pub attack: i8,
     pub defense: i8,
+    pub speed: i8,
     pub accuracy: i8,
     pub evasion: i8,
 }
@@ -85,12 +306,39 @@ impl StatStages {
         Self {
             attack: 0,
             defense: 0,
+            speed: 0,
             accuracy: 0,
             evasion: 0,
         }
     }
 }
 
+impl StatStages {
+    pub fn get(&self, stat: Stat) -> i8 {
+        match stat {
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::Speed => self.speed,
+            Stat::Accuracy => self.accuracy,
+            Stat::Evasion => self.evasion,
+        }
+    }
+
+    /// Returns the stages with `amount` added to the stage of `stat`,
+    /// capped at [`MAX_STAGE`] either way.
+    pub fn modify(mut self, stat: Stat, amount: i8) -> Self {
+        let stage = match stat {
+            Stat::Attack => &mut self.attack,
+            Stat::Defense => &mut self.defense,
+            Stat::Speed => &mut self.speed,
+            Stat::Accuracy => &mut self.accuracy,
+            Stat::Evasion => &mut self.evasion,
+        };
+        *stage = add_stages(*stage, amount);
+        self
+    }
+}
+
 impl Default for StatStages {
     fn default() -> Self {
         Self::new()
@@ -100,31 +348,287 @@ impl Default for StatStages {
 impl Add<StatStages> for StatStages {
     fn add(self, rhs: StatStages) -> Self::Output {
         Self {
-            attack: self.attack + rhs.attack,
-            defense: self.defense + rhs.defense,
-            accuracy: self.accuracy + rhs.accuracy,
-            evasion: self.evasion + rhs.evasion,
+            attack: add_stages(self.attack, rhs.attack),
+            defense: add_stages(self.defense, rhs.defense),
+            speed: add_stages(self.speed, rhs.speed),
+            accuracy: add_stages(self.accuracy, rhs.accuracy),
+            evasion: add_stages(self.evasion, rhs.evasion),
         }
     }
 
     type Output = StatStages;
 }
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Gender {
+    Male,
+    Female,
+    #[default]
+    Genderless,
+}
+
+/// Who a dragon is to the players. None of it changes how the dragon battles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DragonIdentity {
+    /// The name of the species, if the dragon has one.
+    pub species: Option<String>,
+    pub nickname: Option<String>,
+    pub gender: Gender,
+    /// Cosmetic flags, such as "shiny".
+    pub cosmetics: Vec<String>,
+}
+
+impl DragonIdentity {
+    /// The name to show for the dragon: its nickname, or else the name
+    /// of its species.
+    pub fn name(&self) -> &str {
+        self.nickname
+            .as_deref()
+            .or(self.species.as_deref())
+            .unwrap_or("Dragon")
+    }
+
+    pub fn has_cosmetic(&self, cosmetic: &str) -> bool {
+        self.cosmetics.iter().any(|c| c == cosmetic)
+    }
+}
+
+#[derive(Clone)]
 pub struct BattleDragon {
+    species: Option<Arc<DragonData>>,
+    identity: DragonIdentity,
+    /// The stats of the dragon. For dragons of a species, they are
+    /// calculated from the species, the level and the values.
     base_stats: Stats,
+    /// The individual values, which raise the stats of dragons of a species.
+    values: Stats,
+    level: u8,
+    experience: u32,
+    types: Vec<String>,
+    /// The temporary form of the dragon, if it has changed its form.
+    form: Option<FormChange>,
     pub hp: u32,
 }
 
+/// A temporary form, with the stats and types the dragon had before.
+#[derive(Clone)]
+struct FormChange {
+    form: Arc<DragonData>,
+    stats: Stats,
+    types: Vec<String>,
+}
+
 impl BattleDragon {
     pub fn new(base_stats: Stats) -> BattleDragon {
         Self {
+            species: None,
+            identity: DragonIdentity::default(),
             hp: base_stats.calculate_hp(),
             base_stats,
+            values: Stats::zero(),
+            level: 50,
+            experience: 0,
+            types: vec![],
+            form: None,
+        }
+    }
+
+    /// Creates a dragon of a species with its types. Its stats are
+    /// calculated from the base stats of the species, see [`Stats::at_level`].
+    pub fn from_species(species: Arc<DragonData>) -> BattleDragon {
+        let types: Vec<&str> = species.types.iter().map(String::as_str).collect();
+        let mut dragon = Self::new(species.base_stats).with_types(&types);
+        dragon.identity.species = Some(species.name.clone());
+        dragon.experience = species
+            .growth_rate
+            .experience_for(dragon.level)
+            .unwrap_or(0);
+        dragon.species = Some(species);
+        dragon.recalculate_stats();
+        dragon
+    }
+
+    /// The species of the dragon, unless it was created from stats only.
+    pub fn species(&self) -> Option<&DragonData> {
+        self.species.as_deref()
+    }
+
+    pub fn with_nickname(mut self, nickname: &str) -> Self {
+        self.identity.nickname = Some(nickname.to_string());
+        self
+    }
+
+    pub fn with_gender(mut self, gender: Gender) -> Self {
+        self.identity.gender = gender;
+        self
+    }
+
+    pub fn with_cosmetic(mut self, cosmetic: &str) -> Self {
+        self.identity.cosmetics.push(cosmetic.to_string());
+        self
+    }
+
+    pub fn identity(&self) -> &DragonIdentity {
+        &self.identity
+    }
+
+    pub fn nickname(&self) -> Option<&str> {
+        self.identity.nickname.as_deref()
+    }
+
+    /// The name to show for the dragon, see [`DragonIdentity::name`].
+    pub fn name(&self) -> &str {
+        self.identity.name()
+    }
+
+    /// Sets the level of the dragon. Dragons of a species get the
+    /// experience needed for the level, if it can be reached.
+    pub fn with_level(mut self, level: u8) -> Self {
+        self.level = level;
+        if let Some(experience) = self
+            .species
+            .as_ref()
+            .and_then(|species| species.growth_rate.experience_for(level))
+        {
+            self.experience = experience;
+        }
+        self.recalculate_stats();
+        self
+    }
+
+    /// Sets the individual values of the dragon. Only dragons of a
+    /// species have their stats raised by them.
+    pub fn with_values(mut self, values: Stats) -> Self {
+        self.values = values;
+        self.recalculate_stats();
+        self
+    }
+
+    pub fn values(&self) -> &Stats {
+        &self.values
+    }
+
+    pub fn experience(&self) -> u32 {
+        self.experience
+    }
+
+    /// Adds experience to a dragon of a species, raising its level as
+    /// far as the experience reaches. Dragons created from stats only
+    /// do not gain experience. Returns what happened to the dragon.
+    pub fn gain_experience(&mut self, amount: u32) -> Vec<ProgressEvent> {
+        let species = match &self.species {
+            Some(species) => species.clone(),
+            None => return vec![],
+        };
+        self.experience = self.experience.saturating_add(amount);
+        let mut events = vec![ProgressEvent::Experience(amount)];
+        while self.level < MAX_LEVEL
+            && species
+                .growth_rate
+                .experience_for(self.level + 1)
+                .is_some_and(|needed| self.experience >= needed)
+        {
+            self.level += 1;
+            self.recalculate_stats();
+            events.push(ProgressEvent::LevelUp(self.level));
+            events.extend(
+                species
+                    .learnset
+                    .iter()
+                    .filter(|e| e.level == self.level)
+                    .map(|e| ProgressEvent::WantsToLearn(e.move_name.clone())),
+            );
+        }
+        events
+    }
+
+    /// Recalculates the stats of a dragon of a species. The dragon keeps
+    /// the damage it has taken, and stays fainted if it has fainted.
+    fn recalculate_stats(&mut self) {
+        if let Some(species) = &self.species {
+            let damage = self.max_hp() - self.hp.min(self.max_hp());
+            let mut stats = Stats::at_level(species.base_stats, self.values, self.level);
+            if let Some(change) = &self.form {
+                stats = self.form_stats(stats, &change.form);
+            }
+            self.base_stats = stats;
+            if self.hp > 0 {
+                self.hp = self.max_hp().saturating_sub(damage).max(1);
+            }
+        }
+    }
+
+    /// The stats of the dragon in a form. The form does not change the HP.
+    fn form_stats(&self, stats: Stats, form: &DragonData) -> Stats {
+        Stats {
+            hp: stats.hp,
+            ..Stats::at_level(form.base_stats, self.values, self.level)
+        }
+    }
+
+    /// Evolves the dragon into a species. The dragon keeps its level,
+    /// experience and the ratio of its HP to its maximum HP, and goes
+    /// back to its own form.
+    pub fn evolve(&mut self, species: Arc<DragonData>) {
+        self.revert_form();
+        let (hp, max_hp) = (self.hp as u64, self.max_hp() as u64);
+        self.types = species.types.clone();
+        self.identity.species = Some(species.name.clone());
+        self.species = Some(species);
+        self.recalculate_stats();
+        if hp > 0 {
+            self.hp = ((self.max_hp() as u64 * hp / max_hp.max(1)) as u32).max(1);
+        }
+    }
+
+    /// Changes the dragon into a form, such as a mega evolution, taking
+    /// the types of the form and the stats of the form at its level,
+    /// apart from HP. The form lasts until [`revert_form`](Self::revert_form).
+    pub fn change_form(&mut self, form: Arc<DragonData>) {
+        self.revert_form();
+        self.form = Some(FormChange {
+            stats: self.base_stats,
+            types: std::mem::replace(&mut self.types, form.types.clone()),
+            form: form.clone(),
+        });
+        self.base_stats = self.form_stats(self.base_stats, &form);
+    }
+
+    /// Changes the dragon back from its form, if it has one.
+    pub fn revert_form(&mut self) {
+        if let Some(change) = self.form.take() {
+            self.base_stats = change.stats;
+            self.types = change.types;
+            self.recalculate_stats();
         }
     }
 
+    pub fn form(&self) -> Option<&DragonData> {
+        self.form.as_ref().map(|change| &*change.form)
+    }
+
+    pub fn with_types(mut self, types: &[&str]) -> Self {
+        self.types = types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    pub fn has_type(&self, dragon_type: &str) -> bool {
+        self.types.iter().any(|t| t == dragon_type)
+    }
+
     pub fn stats(&self) -> &Stats {
         &self.base_stats
     }
+
+    pub fn max_hp(&self) -> u32 {
+        self.base_stats.calculate_hp()
+    }
 }
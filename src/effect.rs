@@ -1,64 +1,335 @@
-use std::u16;
+use std::num::NonZeroU32;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    dragon::{BattleDragon, StatStages},
-    moves::MoveStats,
+    dragon::{BattleDragon, Stat, StatStages, Stats},
+    events::MoveEvent,
+    moves::{AttackContext, Interception, MoveTrait},
+    party::{PartyItem, RelativePartyId},
+    side::{SideEffectTrait, WeatherData},
 };
 
+/// An operation requested by an effect through an [`EffectContext`].
+pub(crate) enum EffectAction {
+    Damage(RelativePartyId, u32),
+    Heal(RelativePartyId, u32),
+    AddEffect(RelativePartyId, Box<dyn LongTermEffectTrait>),
+    Event(MoveEvent),
+    RemoveItem(RelativePartyId),
+    AddSideEffect(RelativePartyId, Box<dyn SideEffectTrait>),
+    SetWeather(WeatherData),
+}
+
+/// A controlled view of the battle handed to effect hooks. The dragon
+/// owning the effect and its opponent can be inspected, but changes
+/// to the battle are only requested here: they are carried out by the
+/// battlefield in order once the hook returns. Targets are relative to
+/// the dragon owning the effect.
+///
+/// Outside of a battle (for example when an effect is added to a
+/// dragon before the battle starts) there is no opponent, and only the
+/// damage, heal and effect requests targeting the user are carried out.
+pub struct EffectContext<'a> {
+    user: &'a BattleDragon,
+    opponent: Option<&'a PartyItem>,
+    rng: &'a mut dyn RngCore,
+    actions: Vec<EffectAction>,
+}
+
+impl<'a> EffectContext<'a> {
+    pub(crate) fn new(
+        user: &'a BattleDragon,
+        opponent: Option<&'a PartyItem>,
+        rng: &'a mut dyn RngCore,
+    ) -> Self {
+        Self {
+            user,
+            opponent,
+            rng,
+            actions: vec![],
+        }
+    }
+
+    pub(crate) fn into_actions(self) -> Vec<EffectAction> {
+        self.actions
+    }
+
+    /// The dragon the effect is attached to.
+    pub fn user(&self) -> &BattleDragon {
+        self.user
+    }
+
+    /// The active dragon of the opposing party, if the
+    /// effect is used in a battle.
+    pub fn opponent(&self) -> Option<&PartyItem> {
+        self.opponent
+    }
+
+    /// The random number generator of the battle. Effects should use
+    /// this instead of their own, so that seeded battles are repeatable.
+    pub fn rng(&mut self) -> &mut dyn RngCore {
+        self.rng
+    }
+
+    /// Deals `amount` damage to the target.
+    pub fn damage(&mut self, target: RelativePartyId, amount: u32) {
+        self.actions.push(EffectAction::Damage(target, amount));
+    }
+
+    /// Restores `amount` HP of the target, up to its maximum HP.
+    pub fn heal(&mut self, target: RelativePartyId, amount: u32) {
+        self.actions.push(EffectAction::Heal(target, amount));
+    }
+
+    /// Attaches a new effect to the target.
+    pub fn add_effect(&mut self, target: RelativePartyId, effect: Box<dyn LongTermEffectTrait>) {
+        self.actions.push(EffectAction::AddEffect(target, effect));
+    }
+
+    /// Sends an event to the messenger of the battlefield.
+    pub fn emit(&mut self, event: MoveEvent) {
+        self.actions.push(EffectAction::Event(event));
+    }
+
+    /// Removes the held item of the target, for example
+    /// because the item was consumed.
+    pub fn remove_item(&mut self, target: RelativePartyId) {
+        self.actions.push(EffectAction::RemoveItem(target));
+    }
+}
+
+/// What kind of condition an effect is, so that cures can
+/// find the effects they remove.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectCategory {
+    /// A major status condition, such as poison.
+    Status,
+    /// A temporary condition, such as confusion. Volatile conditions
+    /// are removed when the dragon is switched out.
+    Volatile,
+    /// Anything else.
+    Other,
+}
+
+/// A major status condition. A dragon can only have one at a time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusCondition {
+    Poison,
+    Burn,
+    Paralysis,
+    Sleep,
+}
+
+impl StatusCondition {
+    /// Creates the effect of the condition. Sleep lasts
+    /// a random number of turns, from one to three.
+    pub fn effect(self, rng: &mut dyn RngCore) -> Box<dyn LongTermEffectTrait> {
+        match self {
+            StatusCondition::Poison => Box::new(effects::Poison::new(NonZeroU32::new(8).unwrap())),
+            StatusCondition::Burn => Box::new(effects::Burn::new(NonZeroU32::new(16).unwrap())),
+            StatusCondition::Paralysis => Box::new(effects::Paralysis),
+            StatusCondition::Sleep => Box::new(effects::Sleep::new(rng.gen_range(1..=3))),
+        }
+    }
+}
+
+/// A temporary condition restricting what a dragon can do.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolatileCondition {
+    Confusion,
+    /// The dragon can not switch out for the given number of turns.
+    Trap {
+        turns: u16,
+    },
+    /// The dragon can not use status moves for the given number of turns.
+    Taunt {
+        turns: u16,
+    },
+    /// The dragon can only use its last move for the given number of turns.
+    Encore {
+        turns: u16,
+    },
+    /// The last move of the dragon can not be used for the given
+    /// number of turns.
+    Disable {
+        turns: u16,
+    },
+}
+
+impl VolatileCondition {
+    /// Creates the effect of the condition for a target. Returns None
+    /// if the condition needs the last move of the target, but it has
+    /// not used a move yet. Confusion lasts from one to four moves.
+    pub fn effect(
+        self,
+        target: &PartyItem,
+        rng: &mut dyn RngCore,
+    ) -> Option<Box<dyn LongTermEffectTrait>> {
+        Some(match self {
+            VolatileCondition::Confusion => Box::new(effects::Confusion::new(rng.gen_range(1..=4))),
+            VolatileCondition::Trap { turns } => Box::new(effects::Trap::new(turns)),
+            VolatileCondition::Taunt { turns } => Box::new(effects::Taunt::new(turns)),
+            VolatileCondition::Encore { turns } => {
+                Box::new(effects::Encore::new(target.last_move()?, turns))
+            }
+            VolatileCondition::Disable { turns } => {
+                Box::new(effects::Disable::new(target.last_move()?, turns))
+            }
+        })
+    }
+}
+
 /// If a function returns None, the action is stopped. In
 /// other cases, the returned data is used in the action,
 /// possibly passing through other effects before.
+///
+/// The effects of a dragon are always called in order of their
+/// [`priority`](LongTermEffectTrait::priority), highest first. Effects
+/// with the same priority are called in the order they were attached in.
 pub trait LongTermEffectTrait: Send + Sync {
     /// Return a lowercase string corresponding to the type
     /// of the longterm effect.
     fn get_name(&self) -> &str;
 
-    /// Called when the effect is first added to the dragon.
-    /// May mutate state.
-    fn apply(&self, dragon: BattleDragon) -> (bool, BattleDragon) {
-        (true, dragon)
+    /// The name of the effect as shown to players, for example in
+    /// messages about the effect stopping a move.
+    fn display_name(&self) -> &str {
+        self.get_name()
+    }
+
+    /// The priority of the effect, which decides where it is placed
+    /// among the other effects of the dragon. At the end of the turn,
+    /// effects of both active dragons with the same priority are
+    /// called together, starting with the faster dragon.
+    fn priority(&self) -> i8 {
+        0
     }
 
-    /// Called each turn, may add effects to the dragon via returning
-    /// effects in a vector. Returns false if it should be detached.
-    /// Even if the effect is detaching, the other ones in the vector
-    /// will be applied.
-    fn turn(&mut self, _turn: u16) -> (bool, Option<Vec<Box<dyn LongTermEffectTrait>>>) {
-        (true, None)
+    /// The kind of condition the effect is.
+    fn category(&self) -> EffectCategory {
+        EffectCategory::Other
     }
 
+    /// Called when the effect is first added to the dragon. Returns
+    /// false if the effect should not stay attached.
+    fn apply(&self, _ctx: &mut EffectContext) -> bool {
+        true
+    }
+
+    /// Called each turn with the number of turns the effect has been
+    /// attached. Returns false if it should be detached. Effects added
+    /// through the context are attached even if this one is detaching.
+    fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+        true
+    }
+
+    // The hooks below calculate data for a move, and are also used when
+    // only previewing one, so they do not get a context and must not
+    // have side effects.
+
     /// Called when stats are being calculated.
     fn stat_calculation(&self, stages: StatStages) -> StatStages {
         stages
     }
+    /// The stat stage this effect changes and by how much, if it is a
+    /// stage modifier. Modifiers of the same stat are merged into one
+    /// when attached.
+    fn stage_change(&self) -> Option<(Stat, i8)> {
+        None
+    }
+    /// Called on the stats of the dragon after the stat
+    /// stages have been applied to them.
+    fn stat_modification(&self, stats: Stats) -> Stats {
+        stats
+    }
     /// Called when the dragon (user) is attacking an opponent. May return
-    fn offending(
-        &self,
-        stages: StatStages,
-        move_stats: MoveStats,
-        opponent_stages: StatStages,
-    ) -> Option<(StatStages, MoveStats, StatStages)> {
-        Some((stages, move_stats, opponent_stages))
-    }
-    /// Called when the opponent is attacking the dragon.
-    fn defending(
-        &self,
-        stages: StatStages,
-        move_stats: MoveStats,
-        opponent_stages: StatStages,
-    ) -> Option<(StatStages, MoveStats, StatStages)> {
-        Some((stages, move_stats, opponent_stages))
+    /// None to stop the attack.
+    fn offending(&self, attack: AttackContext) -> Option<AttackContext> {
+        Some(attack)
+    }
+    /// Called when a move of the opponent is aimed at the dragon, after
+    /// the `offending` hooks of the opponent's effects and before the
+    /// `defending` hooks. Decides whether the move reaches the dragon.
+    fn intercept(&self, _attack: &AttackContext) -> Interception {
+        Interception::Hit
+    }
+    /// Called when the opponent is attacking the dragon, after the
+    /// `offending` hooks of the opponent's effects.
+    fn defending(&self, attack: AttackContext) -> Option<AttackContext> {
+        Some(attack)
+    }
+    /// Returns false if the dragon may not choose the move, for
+    /// example because the move is disabled.
+    fn allows_move(&self, _attack: &dyn MoveTrait) -> bool {
+        true
+    }
+    /// Returns the name of a move the dragon has to use in its next
+    /// turn instead of the chosen action, for example because it is
+    /// charging the move. Forced moves do not use PP.
+    fn forced_move(&self) -> Option<&str> {
+        None
     }
     /// Called when the dragon is being switched.
     fn switching(&self) -> Option<()> {
         Some(())
     }
+
+    /// Called before a new effect is attached to the dragon. Returning
+    /// false makes the dragon immune to the effect.
+    fn accepts_effect(&self, _effect_name: &str) -> bool {
+        true
+    }
+
+    /// Returns false if the effect can not be removed by cures, for
+    /// example because it comes from an ability.
+    fn removable(&self) -> bool {
+        true
+    }
+
+    /// Called when the dragon enters the battle, either at the start
+    /// of the battle or by being switched in.
+    fn on_switch_in(&mut self, _ctx: &mut EffectContext) {}
+
+    /// Called before the dragon uses a move. Returning false stops
+    /// the move from being used.
+    fn before_move(&mut self, _ctx: &mut EffectContext, _move_name: &str) -> bool {
+        true
+    }
+    /// Called after the dragon has used a move.
+    fn after_move(&mut self, _ctx: &mut EffectContext, _move_name: &str) {}
+    /// Called when the dragon was hit by a move of the opponent.
+    fn on_hit(&self, _ctx: &mut EffectContext, _move_name: &str) {}
+    /// Called when a move of the opponent is about to deal `damage` to
+    /// the dragon. Returns the damage that is left for the HP of the
+    /// dragon, for example after a substitute took the hit.
+    fn absorb(&mut self, damage: u32) -> u32 {
+        damage
+    }
+    /// Called when the dragon lost `amount` HP to a move of the opponent.
+    fn on_damage(&self, _ctx: &mut EffectContext, _amount: u32) {}
+    /// Called when the dragon or its opponent faints. `fainted` is
+    /// relative to the dragon owning the effect.
+    fn on_faint(&self, _ctx: &mut EffectContext, _fainted: RelativePartyId) {}
+    /// Called at the end of each turn, before the effects are
+    /// notified about the new turn.
+    fn end_of_turn(&mut self, _ctx: &mut EffectContext) {}
 }
 
 pub mod effects {
-    use crate::dragon::{BattleDragon, StatStages};
+    use std::num::NonZeroU32;
+
+    use rand::Rng;
 
-    use super::LongTermEffectTrait;
+    use crate::{
+        dragon::{Stat, StatStages, Stats},
+        events::MoveEvent,
+        moves::{AttackContext, Interception, MoveTrait, SemiInvulnerable},
+        party::RelativePartyId,
+    };
+
+    use super::{EffectCategory, EffectContext, LongTermEffectTrait};
 
     /// Modifies the attack stat stage.
     pub struct AttackStageModifier(i8);
@@ -69,10 +340,11 @@ pub mod effects {
     }
     impl LongTermEffectTrait for AttackStageModifier {
         fn stat_calculation(&self, stages: StatStages) -> StatStages {
-            StatStages {
-                attack: stages.attack + self.0,
-                ..stages
-            }
+            stages.modify(Stat::Attack, self.0)
+        }
+
+        fn stage_change(&self) -> Option<(Stat, i8)> {
+            Some((Stat::Attack, self.0))
         }
 
         fn get_name(&self) -> &str {
@@ -80,6 +352,45 @@ pub mod effects {
         }
     }
 
+    /// Modifies the stage of any stat.
+    pub struct StatStageModifier(Stat, i8);
+    impl StatStageModifier {
+        pub fn new(stat: Stat, amount: i8) -> Self {
+            Self(stat, amount)
+        }
+    }
+    impl LongTermEffectTrait for StatStageModifier {
+        fn stat_calculation(&self, stages: StatStages) -> StatStages {
+            stages.modify(self.0, self.1)
+        }
+
+        fn stage_change(&self) -> Option<(Stat, i8)> {
+            Some((self.0, self.1))
+        }
+
+        fn get_name(&self) -> &str {
+            "stat_modifier"
+        }
+    }
+
+    /// Raises the crit stage of the moves of the dragon.
+    pub struct CritStageModifier(u8);
+    impl CritStageModifier {
+        pub fn new(amount: u8) -> Self {
+            Self(amount)
+        }
+    }
+    impl LongTermEffectTrait for CritStageModifier {
+        fn offending(&self, mut attack: AttackContext) -> Option<AttackContext> {
+            attack.move_stats.crit_calc = attack.move_stats.crit_calc.saturating_add(self.0);
+            Some(attack)
+        }
+
+        fn get_name(&self) -> &str {
+            "crit_modifier"
+        }
+    }
+
     /// Modifies the defense stat stage
     pub struct DefenseStageModifier(i8);
     impl DefenseStageModifier {
@@ -89,11 +400,13 @@ pub mod effects {
     }
     impl LongTermEffectTrait for DefenseStageModifier {
         fn stat_calculation(&self, stages: StatStages) -> StatStages {
-            StatStages {
-                defense: stages.defense + self.0,
-                ..stages
-            }
+            stages.modify(Stat::Defense, self.0)
+        }
+
+        fn stage_change(&self) -> Option<(Stat, i8)> {
+            Some((Stat::Defense, self.0))
         }
+
         fn get_name(&self) -> &str {
             "defense_modifier"
         }
@@ -101,21 +414,675 @@ pub mod effects {
 
     /// Calls a closure once it is attached to a BattleDragon, then
     /// removes itself.
-    pub struct OneshotEffect<T: Fn(BattleDragon) -> BattleDragon + Send + Sync>(T);
-    impl<T: Fn(BattleDragon) -> BattleDragon + Send + Sync> OneshotEffect<T> {
+    pub struct OneshotEffect<T: Fn(&mut EffectContext) + Send + Sync>(T);
+    impl<T: Fn(&mut EffectContext) + Send + Sync> OneshotEffect<T> {
         pub fn new(modifier: T) -> Self {
             Self(modifier)
         }
     }
 
-    impl<T: Fn(BattleDragon) -> BattleDragon + Send + Sync> LongTermEffectTrait for OneshotEffect<T> {
+    impl<T: Fn(&mut EffectContext) + Send + Sync> LongTermEffectTrait for OneshotEffect<T> {
         fn get_name(&self) -> &str {
             "oneshot"
         }
 
-        fn apply(&self, dragon: BattleDragon) -> (bool, BattleDragon) {
-            let dragon = self.0(dragon);
-            (false, dragon)
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            self.0(ctx);
+            false
+        }
+    }
+
+    /// Damages the dragon by `1 / denominator` of its maximum
+    /// HP at the end of each turn.
+    pub struct Poison(NonZeroU32);
+    impl Poison {
+        pub fn new(denominator: NonZeroU32) -> Self {
+            Self(denominator)
+        }
+    }
+
+    impl LongTermEffectTrait for Poison {
+        fn get_name(&self) -> &str {
+            "poison"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Status
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "was poisoned".to_string(),
+            });
+            true
+        }
+
+        fn end_of_turn(&mut self, ctx: &mut EffectContext) {
+            let amount = ctx.user().max_hp() / self.0;
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "is hurt by poison".to_string(),
+            });
+            ctx.damage(RelativePartyId::User, amount.max(1));
+        }
+    }
+
+    /// Damages the dragon by `1 / denominator` of its maximum HP at
+    /// the end of each turn, and halves its attack.
+    pub struct Burn(NonZeroU32);
+    impl Burn {
+        pub fn new(denominator: NonZeroU32) -> Self {
+            Self(denominator)
+        }
+    }
+
+    impl LongTermEffectTrait for Burn {
+        fn get_name(&self) -> &str {
+            "burn"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Status
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "was burned".to_string(),
+            });
+            true
+        }
+
+        fn stat_modification(&self, stats: Stats) -> Stats {
+            Stats {
+                attack: stats.attack / 2,
+                ..stats
+            }
+        }
+
+        fn end_of_turn(&mut self, ctx: &mut EffectContext) {
+            let amount = ctx.user().max_hp() / self.0;
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "is hurt by its burn".to_string(),
+            });
+            ctx.damage(RelativePartyId::User, amount.max(1));
+        }
+    }
+
+    /// Halves the speed of the dragon, and stops it from
+    /// moving with a chance of 1 in 4.
+    pub struct Paralysis;
+    impl LongTermEffectTrait for Paralysis {
+        fn get_name(&self) -> &str {
+            "paralysis"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Status
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "is paralyzed".to_string(),
+            });
+            true
+        }
+
+        fn stat_modification(&self, stats: Stats) -> Stats {
+            Stats {
+                speed: stats.speed / 2,
+                ..stats
+            }
+        }
+
+        fn before_move(&mut self, ctx: &mut EffectContext, _move_name: &str) -> bool {
+            if ctx.rng().gen_ratio(1, 4) {
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: "is fully paralyzed".to_string(),
+                });
+                return false;
+            }
+            true
+        }
+    }
+
+    /// Stops the dragon from moving for the given number of its
+    /// moves. It wakes up when it tries to move after that.
+    pub struct Sleep {
+        turns: u8,
+        awake: bool,
+    }
+    impl Sleep {
+        pub fn new(turns: u8) -> Self {
+            Self {
+                turns,
+                awake: false,
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Sleep {
+        fn get_name(&self) -> &str {
+            "sleep"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Status
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "fell asleep".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            !self.awake
+        }
+
+        fn before_move(&mut self, ctx: &mut EffectContext, _move_name: &str) -> bool {
+            if self.awake {
+                return true;
+            }
+            if self.turns == 0 {
+                self.awake = true;
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: "woke up".to_string(),
+                });
+                return true;
+            }
+            self.turns -= 1;
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "is fast asleep".to_string(),
+            });
+            false
+        }
+    }
+
+    /// Stops the dragon from moving for the rest of the turn.
+    pub struct Flinch;
+    impl LongTermEffectTrait for Flinch {
+        fn get_name(&self) -> &str {
+            "flinch"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            false
+        }
+
+        fn before_move(&mut self, ctx: &mut EffectContext, _move_name: &str) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "flinched".to_string(),
+            });
+            false
+        }
+    }
+
+    /// Stops every move used against the dragon
+    /// for the rest of the turn.
+    pub struct Protect;
+    impl LongTermEffectTrait for Protect {
+        fn get_name(&self) -> &str {
+            "protect"
+        }
+
+        fn display_name(&self) -> &str {
+            "Protect"
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "protected itself".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            false
+        }
+
+        fn intercept(&self, _attack: &AttackContext) -> Interception {
+            Interception::Blocked
+        }
+    }
+
+    /// A decoy taking the hits of damaging moves in place of the
+    /// dragon, until its HP runs out. It also blocks status moves, and
+    /// the secondary effects and `on_hit` hooks of the hits it takes.
+    /// Its HP is paid by the dragon when it is put up.
+    pub struct Substitute {
+        hp: u32,
+    }
+    impl Substitute {
+        pub fn new(hp: u32) -> Self {
+            Self { hp }
+        }
+    }
+
+    impl LongTermEffectTrait for Substitute {
+        fn get_name(&self) -> &str {
+            "substitute"
+        }
+
+        fn display_name(&self) -> &str {
+            "its substitute"
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            if ctx.user().hp <= self.hp {
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: "is too weak to make a substitute".to_string(),
+                });
+                return false;
+            }
+            ctx.damage(RelativePartyId::User, self.hp);
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "put in a substitute".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            self.hp > 0
+        }
+
+        fn intercept(&self, attack: &AttackContext) -> Interception {
+            if self.hp > 0 && attack.flags.status {
+                Interception::Blocked
+            } else {
+                Interception::Hit
+            }
+        }
+
+        /// Damage beyond the HP of the substitute is not carried over.
+        fn absorb(&mut self, damage: u32) -> u32 {
+            if self.hp == 0 {
+                return damage;
+            }
+            self.hp = self.hp.saturating_sub(damage);
+            0
+        }
+    }
+
+    /// Pulls the moves aimed at the dragon to a slot of the field for
+    /// the rest of the turn. The slot is relative to the dragon.
+    pub struct Redirect(RelativePartyId);
+    impl Redirect {
+        pub fn new(slot: RelativePartyId) -> Self {
+            Self(slot)
+        }
+    }
+
+    impl LongTermEffectTrait for Redirect {
+        fn get_name(&self) -> &str {
+            "redirect"
+        }
+
+        fn display_name(&self) -> &str {
+            "Redirect"
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            false
+        }
+
+        fn intercept(&self, _attack: &AttackContext) -> Interception {
+            Interception::Redirected(self.0)
+        }
+    }
+
+    /// Makes the dragon hurt itself instead of moving with a chance of
+    /// 1 in 3, for the given number of its moves.
+    pub struct Confusion {
+        turns: u8,
+        done: bool,
+    }
+    impl Confusion {
+        pub fn new(turns: u8) -> Self {
+            Self { turns, done: false }
+        }
+    }
+
+    impl LongTermEffectTrait for Confusion {
+        fn get_name(&self) -> &str {
+            "confusion"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "became confused".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            !self.done
+        }
+
+        fn before_move(&mut self, ctx: &mut EffectContext, _move_name: &str) -> bool {
+            if self.done {
+                return true;
+            }
+            if self.turns == 0 {
+                self.done = true;
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: "snapped out of its confusion".to_string(),
+                });
+                return true;
+            }
+            self.turns -= 1;
+            if ctx.rng().gen_ratio(1, 3) {
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: "hurt itself in its confusion".to_string(),
+                });
+                let amount = ctx.user().max_hp() / 8;
+                ctx.damage(RelativePartyId::User, amount.max(1));
+                return false;
+            }
+            true
+        }
+    }
+
+    /// Attached while the dragon charges a move, forcing it to use the
+    /// move in its next turn. While charging, the dragon may be out of
+    /// reach of every move that can not reach its position. The charge
+    /// is lost if the dragon can not move in its next turn.
+    pub struct Charging {
+        move_name: String,
+        position: Option<SemiInvulnerable>,
+    }
+    impl Charging {
+        pub fn new(move_name: &str, position: Option<SemiInvulnerable>) -> Self {
+            Self {
+                move_name: move_name.to_string(),
+                position,
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Charging {
+        fn get_name(&self) -> &str {
+            "charging"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        /// The move is used in the turn after the charge, which
+        /// removes this effect.
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn == 0
+        }
+
+        fn forced_move(&self) -> Option<&str> {
+            Some(&self.move_name)
+        }
+
+        fn defending(&self, attack: AttackContext) -> Option<AttackContext> {
+            match self.position {
+                Some(position) if attack.flags.reaches != Some(position) => None,
+                _ => Some(attack),
+            }
+        }
+    }
+
+    /// Stops the dragon from moving in its next turn after it used
+    /// a powerful move.
+    pub struct Recharge {
+        move_name: String,
+    }
+    impl Recharge {
+        pub fn new(move_name: &str) -> Self {
+            Self {
+                move_name: move_name.to_string(),
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Recharge {
+        fn get_name(&self) -> &str {
+            "recharge"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn forced_move(&self) -> Option<&str> {
+            Some(&self.move_name)
+        }
+
+        /// The dragon recharges in the turn after the move was used.
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn == 0
+        }
+
+        fn before_move(&mut self, ctx: &mut EffectContext, _move_name: &str) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "must recharge".to_string(),
+            });
+            false
+        }
+    }
+
+    /// Locks the dragon into a move for the given number of uses,
+    /// then leaves it confused.
+    pub struct Rampage {
+        move_name: String,
+        uses: u8,
+    }
+    impl Rampage {
+        pub fn new(move_name: &str, uses: u8) -> Self {
+            Self {
+                move_name: move_name.to_string(),
+                uses,
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Rampage {
+        fn get_name(&self) -> &str {
+            "rampage"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn forced_move(&self) -> Option<&str> {
+            if self.uses > 0 {
+                Some(&self.move_name)
+            } else {
+                None
+            }
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, _turn: u16) -> bool {
+            self.uses > 0
+        }
+
+        fn after_move(&mut self, ctx: &mut EffectContext, move_name: &str) {
+            if move_name != self.move_name || self.uses == 0 {
+                return;
+            }
+            self.uses -= 1;
+            if self.uses == 0 {
+                let turns = ctx.rng().gen_range(1..=4);
+                ctx.add_effect(RelativePartyId::User, Box::new(Confusion::new(turns)));
+            }
+        }
+    }
+
+    /// Stops the dragon from switching out for a number of turns.
+    pub struct Trap(u16);
+    impl Trap {
+        pub fn new(turns: u16) -> Self {
+            Self(turns)
+        }
+    }
+
+    impl LongTermEffectTrait for Trap {
+        fn get_name(&self) -> &str {
+            "trap"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "can not escape".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn + 1 < self.0
+        }
+
+        fn switching(&self) -> Option<()> {
+            None
+        }
+    }
+
+    /// Stops the dragon from using status moves for a number of turns.
+    pub struct Taunt(u16);
+    impl Taunt {
+        pub fn new(turns: u16) -> Self {
+            Self(turns)
+        }
+    }
+
+    impl LongTermEffectTrait for Taunt {
+        fn get_name(&self) -> &str {
+            "taunt"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "fell for the taunt".to_string(),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn + 1 < self.0
+        }
+
+        fn allows_move(&self, attack: &dyn MoveTrait) -> bool {
+            !attack.is_status()
+        }
+    }
+
+    /// Only lets the dragon use one move for a number of turns.
+    pub struct Encore {
+        move_name: String,
+        turns: u16,
+    }
+    impl Encore {
+        pub fn new(move_name: &str, turns: u16) -> Self {
+            Self {
+                move_name: move_name.to_string(),
+                turns,
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Encore {
+        fn get_name(&self) -> &str {
+            "encore"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: format!("has to repeat {}", self.move_name),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn + 1 < self.turns
+        }
+
+        fn allows_move(&self, attack: &dyn MoveTrait) -> bool {
+            attack.get_name() == self.move_name
+        }
+    }
+
+    /// Stops the dragon from using one of its moves for a number of turns.
+    pub struct Disable {
+        move_name: String,
+        turns: u16,
+    }
+    impl Disable {
+        pub fn new(move_name: &str, turns: u16) -> Self {
+            Self {
+                move_name: move_name.to_string(),
+                turns,
+            }
+        }
+    }
+
+    impl LongTermEffectTrait for Disable {
+        fn get_name(&self) -> &str {
+            "disable"
+        }
+
+        fn category(&self) -> EffectCategory {
+            EffectCategory::Volatile
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            ctx.emit(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: format!("can not use {} anymore", self.move_name),
+            });
+            true
+        }
+
+        fn turn(&mut self, _ctx: &mut EffectContext, turn: u16) -> bool {
+            turn + 1 < self.turns
+        }
+
+        fn allows_move(&self, attack: &dyn MoveTrait) -> bool {
+            attack.get_name() != self.move_name
         }
     }
 }
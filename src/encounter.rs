@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::Dex,
+    dragon::{BattleDragon, DragonData, Stats, MAX_LEVEL, MAX_VALUE},
+    error::{EngineError, Result},
+    party::PartyItem,
+};
+
+/// The number of moves a wild dragon knows: the last ones it learned.
+const WILD_MOVES: usize = 4;
+
+/// Changes how often a species appears while a condition holds, such
+/// as the time of day or the weather. Conditions are plain names like
+/// `"night"` which the game decides on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncounterModifier {
+    pub condition: String,
+    /// The weight of the species while the condition holds. A weight
+    /// of zero keeps the species from appearing.
+    pub weight: u32,
+}
+
+/// A species which may appear in a zone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncounterSlot {
+    pub species: String,
+    /// How often the species appears, relative to the other species
+    /// of the zone.
+    pub weight: u32,
+    pub min_level: u8,
+    pub max_level: u8,
+    /// The first modifier whose condition holds replaces the weight.
+    #[serde(default)]
+    pub modifiers: Vec<EncounterModifier>,
+}
+
+impl EncounterSlot {
+    /// The weight of the species while the conditions hold.
+    pub fn weight(&self, conditions: &[&str]) -> u32 {
+        self.modifiers
+            .iter()
+            .find(|m| conditions.contains(&m.condition.as_str()))
+            .map_or(self.weight, |m| m.weight)
+    }
+}
+
+/// The wild dragons of a zone, as loaded from data.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Encounter {
+    pub zone: String,
+    #[serde(default)]
+    pub slots: Vec<EncounterSlot>,
+}
+
+impl Encounter {
+    pub fn new(zone: &str) -> Self {
+        Self {
+            zone: zone.to_string(),
+            slots: vec![],
+        }
+    }
+
+    /// Adds a species appearing from `min_level` to `max_level`.
+    pub fn with_species(
+        mut self,
+        species: &str,
+        weight: u32,
+        min_level: u8,
+        max_level: u8,
+    ) -> Self {
+        self.slots.push(EncounterSlot {
+            species: species.to_string(),
+            weight,
+            min_level,
+            max_level,
+            modifiers: vec![],
+        });
+        self
+    }
+
+    /// Sets the weight of the last added species while the condition holds.
+    pub fn with_modifier(mut self, condition: &str, weight: u32) -> Self {
+        if let Some(slot) = self.slots.last_mut() {
+            slot.modifiers.push(EncounterModifier {
+                condition: condition.to_string(),
+                weight,
+            });
+        }
+        self
+    }
+
+    /// Picks a species by weight and a level in its range. Returns None
+    /// if no species may appear while the conditions hold.
+    pub fn choose(
+        &self,
+        conditions: &[&str],
+        rng: &mut dyn RngCore,
+    ) -> Option<(&EncounterSlot, u8)> {
+        // Summed as u64 so that large weights can not overflow.
+        let total: u64 = self.slots.iter().map(|s| s.weight(conditions) as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        let slot = self.slots.iter().find(|slot| {
+            let weight = slot.weight(conditions) as u64;
+            if roll < weight {
+                return true;
+            }
+            roll -= weight;
+            false
+        })?;
+        let low = slot.min_level.min(slot.max_level);
+        let level = rng.gen_range(low..=slot.max_level.max(low));
+        Some((slot, level.clamp(1, MAX_LEVEL)))
+    }
+
+    /// Generates a wild dragon of the zone, see [`wild_dragon`]. Returns
+    /// None if no species may appear while the conditions hold.
+    pub fn generate(
+        &self,
+        dex: &Dex,
+        conditions: &[&str],
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<PartyItem>> {
+        let (slot, level) = match self.choose(conditions, rng) {
+            Some(choice) => choice,
+            None => return Ok(None),
+        };
+        let species = dex
+            .species(&slot.species)
+            .ok_or_else(|| EngineError::NotInDex {
+                kind: "species",
+                name: slot.species.clone(),
+            })?;
+        wild_dragon(species.clone(), level, dex, rng).map(Some)
+    }
+}
+
+/// Creates a wild dragon of the species at the level, with random
+/// individual values and one of the abilities of the species. It knows
+/// the last moves of its learnset up to the level. The moves and the
+/// ability are looked up in the dex.
+pub fn wild_dragon(
+    species: Arc<DragonData>,
+    level: u8,
+    dex: &Dex,
+    rng: &mut dyn RngCore,
+) -> Result<PartyItem> {
+    let values = Stats {
+        hp: rng.gen_range(0..=MAX_VALUE),
+        attack: rng.gen_range(0..=MAX_VALUE),
+        defense: rng.gen_range(0..=MAX_VALUE),
+        speed: rng.gen_range(0..=MAX_VALUE),
+    };
+    let ability = match species.abilities.choose(rng) {
+        Some(name) => Some(dex.ability(name).ok_or_else(|| EngineError::NotInDex {
+            kind: "ability",
+            name: name.clone(),
+        })?),
+        None => None,
+    };
+
+    let mut learned = species
+        .learnset
+        .iter()
+        .filter(|e| e.level <= level)
+        .collect::<Vec<_>>();
+    learned.sort_by_key(|e| e.level);
+    let mut move_names: Vec<&str> = vec![];
+    for entry in learned.iter().rev() {
+        if move_names.len() < WILD_MOVES && !move_names.contains(&entry.move_name.as_str()) {
+            move_names.push(&entry.move_name);
+        }
+    }
+
+    let dragon = BattleDragon::from_species(species.clone())
+        .with_level(level)
+        .with_values(values);
+    let mut item = PartyItem::new(dragon);
+    if let Some(ability) = ability {
+        item = item.with_ability(ability.clone());
+    }
+    for name in move_names.into_iter().rev() {
+        let entry = dex.get_move(name).ok_or_else(|| EngineError::NotInDex {
+            kind: "move",
+            name: name.to_string(),
+        })?;
+        item = item.with_move(entry.attack.clone(), entry.max_pp);
+    }
+    Ok(item)
+}
@@ -0,0 +1,101 @@
+use std::fmt::Display;
+
+/// The errors of the public API, so that bad input can be rejected
+/// instead of crashing the engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineError {
+    /// A party has no members.
+    EmptyParty,
+    /// There is no party with the ID.
+    InvalidPartyId(u8),
+    /// A party chose an action it may not take.
+    IllegalAction(IllegalAction),
+    /// Text could not be read. Lines are counted from 1.
+    Parse { line: usize, message: String },
+    /// The party member has no species, so it can not be written out.
+    MissingSpecies(usize),
+    /// A species, move, item or ability is not in the dex.
+    NotInDex { kind: &'static str, name: String },
+}
+
+pub type Result<T> = std::result::Result<T, EngineError>;
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::EmptyParty => write!(f, "the party has no members"),
+            EngineError::InvalidPartyId(id) => write!(f, "there is no party with ID {}", id),
+            EngineError::IllegalAction(action) => action.fmt(f),
+            EngineError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            EngineError::MissingSpecies(member) => {
+                write!(f, "party member {} has no species", member)
+            }
+            EngineError::NotInDex { kind, name } => {
+                write!(f, "there is no {} \"{}\" in the dex", kind, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<IllegalAction> for EngineError {
+    fn from(action: IllegalAction) -> Self {
+        EngineError::IllegalAction(action)
+    }
+}
+
+/// The reason an action was rejected, see
+/// [`Battlefield::submit_action`](crate::battle::Battlefield::submit_action).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IllegalAction {
+    /// The active dragon has no move in the slot.
+    NoSuchMove(usize),
+    /// The move in the slot has no PP left.
+    NoPpLeft(usize),
+    /// An effect of the active dragon forbids the move in the slot.
+    MoveBlocked { slot: usize, effect: String },
+    /// The party has no member at the index.
+    NoSuchMember(usize),
+    /// The party member is already the active dragon.
+    AlreadyActive(usize),
+    /// The party member has fainted.
+    Fainted(usize),
+    /// An effect of the active dragon stops it from switching out.
+    Trapped,
+    /// The active dragon has to use a move, so it can only submit one.
+    MoveForced(usize),
+    /// The bag item may not be used in this battle.
+    ItemNotAllowed(String),
+    /// The party has none of the bag item left.
+    ItemNotOwned(String),
+}
+
+impl Display for IllegalAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalAction::NoSuchMove(slot) => write!(f, "there is no move in slot {}", slot),
+            IllegalAction::NoPpLeft(slot) => write!(f, "the move in slot {} has no PP left", slot),
+            IllegalAction::MoveBlocked { slot, effect } => {
+                write!(f, "the move in slot {} is blocked by {}", slot, effect)
+            }
+            IllegalAction::NoSuchMember(index) => {
+                write!(f, "there is no party member at index {}", index)
+            }
+            IllegalAction::AlreadyActive(index) => {
+                write!(f, "the party member at index {} is already active", index)
+            }
+            IllegalAction::Fainted(index) => {
+                write!(f, "the party member at index {} has fainted", index)
+            }
+            IllegalAction::Trapped => write!(f, "the active dragon can not switch out"),
+            IllegalAction::MoveForced(slot) => {
+                write!(f, "the active dragon has to use the move in slot {}", slot)
+            }
+            IllegalAction::ItemNotAllowed(item) => write!(f, "{} may not be used", item),
+            IllegalAction::ItemNotOwned(item) => write!(f, "there is no {} left", item),
+        }
+    }
+}
+
+impl std::error::Error for IllegalAction {}
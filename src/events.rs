@@ -1,4 +1,4 @@
-use crate::party::RelativePartyId;
+use crate::{damage::DamageBreakdown, party::RelativePartyId};
 
 pub enum Event {
     Damaged {
@@ -17,12 +17,47 @@ pub enum Event {
 }
 
 pub enum MoveEvent {
+    /// The damage of an attack against the dragon was calculated.
+    Calculated {
+        rel_party_id: RelativePartyId,
+        breakdown: DamageBreakdown,
+    },
     Damaged {
         rel_party_id: RelativePartyId,
         damage_amount: u32,
+        /// The damage comes from a critical hit.
+        critical: bool,
+    },
+    Healed {
+        rel_party_id: RelativePartyId,
+        heal_amount: u32,
     },
     Effected {
         rel_party_id: RelativePartyId,
         description: String,
     },
+    /// The held item of the dragon has activated.
+    ItemActivated {
+        rel_party_id: RelativePartyId,
+        item_name: String,
+    },
+    /// A message about the dragon, such as "is hurt by poison".
+    Message {
+        rel_party_id: RelativePartyId,
+        message: String,
+    },
+}
+
+/// What happens to a dragon as it gains experience.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The dragon gained the amount of experience.
+    Experience(u32),
+    /// The dragon reached the level.
+    LevelUp(u8),
+    /// The dragon reached a level at which its species learns the move.
+    /// The move has to be taught with [`PartyItem::learn_move`].
+    ///
+    /// [`PartyItem::learn_move`]: crate::party::PartyItem::learn_move
+    WantsToLearn(String),
 }
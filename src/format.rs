@@ -0,0 +1,347 @@
+use std::{collections::HashSet, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::party::{Party, PartyItem};
+
+/// The rules a team has to follow to enter a battle, as loaded from
+/// data. Every rule is off unless set.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Format {
+    pub name: String,
+    #[serde(default)]
+    pub min_party_size: Option<usize>,
+    #[serde(default)]
+    pub max_party_size: Option<usize>,
+    #[serde(default)]
+    pub max_level: Option<u8>,
+    #[serde(default)]
+    pub banned_species: Vec<String>,
+    #[serde(default)]
+    pub banned_moves: Vec<String>,
+    #[serde(default)]
+    pub banned_items: Vec<String>,
+    #[serde(default)]
+    pub banned_abilities: Vec<String>,
+    /// No two members may be of the same species.
+    #[serde(default)]
+    pub species_clause: bool,
+    /// No two members may hold the same item.
+    #[serde(default)]
+    pub item_clause: bool,
+    /// Every member needs a species, which has to be able to learn
+    /// its moves and have its ability.
+    #[serde(default)]
+    pub check_learnsets: bool,
+    /// The highest value a single stat may have.
+    #[serde(default)]
+    pub max_stat: Option<u32>,
+    /// The highest value the sum of the stats may have.
+    #[serde(default)]
+    pub max_stat_total: Option<u32>,
+    /// The highest individual value a single stat may have, usually
+    /// [`MAX_VALUE`](crate::dragon::MAX_VALUE).
+    #[serde(default)]
+    pub max_value: Option<u32>,
+}
+
+/// A broken rule of a [`Format`]. `member` is the index of the party
+/// member breaking it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    PartyTooSmall {
+        size: usize,
+        min: usize,
+    },
+    PartyTooLarge {
+        size: usize,
+        max: usize,
+    },
+    LevelTooHigh {
+        member: usize,
+        level: u8,
+        max: u8,
+    },
+    /// The member was created from stats only, so its species is unknown.
+    UnknownSpecies {
+        member: usize,
+    },
+    BannedSpecies {
+        member: usize,
+        species: String,
+    },
+    BannedMove {
+        member: usize,
+        move_name: String,
+    },
+    BannedItem {
+        member: usize,
+        item: String,
+    },
+    BannedAbility {
+        member: usize,
+        ability: String,
+    },
+    DuplicateSpecies {
+        member: usize,
+        species: String,
+    },
+    DuplicateItem {
+        member: usize,
+        item: String,
+    },
+    /// The species of the member can not learn the move at its level.
+    IllegalMove {
+        member: usize,
+        move_name: String,
+    },
+    /// The species of the member can not have the ability.
+    IllegalAbility {
+        member: usize,
+        ability: String,
+    },
+    StatTooHigh {
+        member: usize,
+        stat: &'static str,
+        value: u32,
+        max: u32,
+    },
+    StatTotalTooHigh {
+        member: usize,
+        total: u32,
+        max: u32,
+    },
+    ValueTooHigh {
+        member: usize,
+        stat: &'static str,
+        value: u32,
+        max: u32,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::PartyTooSmall { size, min } => {
+                write!(
+                    f,
+                    "the party has {} members, at least {} are needed",
+                    size, min
+                )
+            }
+            Violation::PartyTooLarge { size, max } => {
+                write!(
+                    f,
+                    "the party has {} members, at most {} are allowed",
+                    size, max
+                )
+            }
+            Violation::LevelTooHigh { member, level, max } => write!(
+                f,
+                "member {} is level {}, the highest allowed level is {}",
+                member, level, max
+            ),
+            Violation::UnknownSpecies { member } => {
+                write!(f, "the species of member {} is unknown", member)
+            }
+            Violation::BannedSpecies { member, species } => {
+                write!(f, "member {} is a {}, which is banned", member, species)
+            }
+            Violation::BannedMove { member, move_name } => {
+                write!(f, "member {} knows {}, which is banned", member, move_name)
+            }
+            Violation::BannedItem { member, item } => {
+                write!(f, "member {} holds {}, which is banned", member, item)
+            }
+            Violation::BannedAbility { member, ability } => {
+                write!(f, "member {} has {}, which is banned", member, ability)
+            }
+            Violation::DuplicateSpecies { member, species } => {
+                write!(f, "member {} is another {}", member, species)
+            }
+            Violation::DuplicateItem { member, item } => {
+                write!(f, "member {} holds another {}", member, item)
+            }
+            Violation::IllegalMove { member, move_name } => {
+                write!(f, "member {} can not learn {}", member, move_name)
+            }
+            Violation::IllegalAbility { member, ability } => {
+                write!(f, "member {} can not have {}", member, ability)
+            }
+            Violation::StatTooHigh {
+                member,
+                stat,
+                value,
+                max,
+            } => write!(
+                f,
+                "the {} of member {} is {}, at most {} is allowed",
+                stat, member, value, max
+            ),
+            Violation::StatTotalTooHigh { member, total, max } => write!(
+                f,
+                "the stats of member {} add up to {}, at most {} is allowed",
+                member, total, max
+            ),
+            Violation::ValueTooHigh {
+                member,
+                stat,
+                value,
+                max,
+            } => write!(
+                f,
+                "the {} value of member {} is {}, at most {} is allowed",
+                stat, member, value, max
+            ),
+        }
+    }
+}
+
+impl Format {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Checks a party against every rule of the format. Returns the
+    /// broken rules, which is empty if the party may enter a battle.
+    pub fn validate(&self, party: &Party) -> Vec<Violation> {
+        let mut violations = vec![];
+        let size = party.len();
+        match (self.min_party_size, self.max_party_size) {
+            (Some(min), _) if size < min => violations.push(Violation::PartyTooSmall { size, min }),
+            (_, Some(max)) if size > max => violations.push(Violation::PartyTooLarge { size, max }),
+            _ => {}
+        }
+
+        let mut species_seen = HashSet::new();
+        let mut items_seen = HashSet::new();
+        for (member, item) in party.members().iter().enumerate() {
+            self.validate_member(member, item, &mut violations);
+            if let Some(species) = item.dragon.species() {
+                if self.species_clause && !species_seen.insert(species.name.as_str()) {
+                    violations.push(Violation::DuplicateSpecies {
+                        member,
+                        species: species.name.clone(),
+                    });
+                }
+            }
+            if let Some(held) = item.item() {
+                if self.item_clause && !items_seen.insert(held.name.as_str()) {
+                    violations.push(Violation::DuplicateItem {
+                        member,
+                        item: held.name.clone(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Checks the rules concerning a single party member.
+    fn validate_member(&self, member: usize, item: &PartyItem, violations: &mut Vec<Violation>) {
+        let level = item.dragon.level();
+        if let Some(max) = self.max_level.filter(|&max| level > max) {
+            violations.push(Violation::LevelTooHigh { member, level, max });
+        }
+
+        let species = item.dragon.species();
+        match species {
+            Some(species) if self.banned_species.contains(&species.name) => {
+                violations.push(Violation::BannedSpecies {
+                    member,
+                    species: species.name.clone(),
+                })
+            }
+            None if self.check_learnsets || self.species_clause => {
+                violations.push(Violation::UnknownSpecies { member })
+            }
+            _ => {}
+        }
+
+        for move_slot in item.moves() {
+            let move_name = move_slot.attack().get_name();
+            if self.banned_moves.iter().any(|m| m == move_name) {
+                violations.push(Violation::BannedMove {
+                    member,
+                    move_name: move_name.to_string(),
+                });
+            }
+            if let Some(species) = species.filter(|_| self.check_learnsets) {
+                if !species.can_learn(move_name, level) {
+                    violations.push(Violation::IllegalMove {
+                        member,
+                        move_name: move_name.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Some(held) = item.item() {
+            if self.banned_items.contains(&held.name) {
+                violations.push(Violation::BannedItem {
+                    member,
+                    item: held.name.clone(),
+                });
+            }
+        }
+        if let Some(ability) = item.ability() {
+            if self.banned_abilities.contains(&ability.name) {
+                violations.push(Violation::BannedAbility {
+                    member,
+                    ability: ability.name.clone(),
+                });
+            }
+            if let Some(species) = species.filter(|_| self.check_learnsets) {
+                if !species.can_have_ability(&ability.name) {
+                    violations.push(Violation::IllegalAbility {
+                        member,
+                        ability: ability.name.clone(),
+                    });
+                }
+            }
+        }
+
+        let stats = item.dragon.stats();
+        if let Some(max) = self.max_stat {
+            let named = [
+                ("attack", stats.attack),
+                ("defense", stats.defense),
+                ("hp", stats.hp),
+                ("speed", stats.speed),
+            ];
+            for (stat, value) in named.iter().copied().filter(|&(_, v)| v > max) {
+                violations.push(Violation::StatTooHigh {
+                    member,
+                    stat,
+                    value,
+                    max,
+                });
+            }
+        }
+        let total = stats.attack + stats.defense + stats.hp + stats.speed;
+        if let Some(max) = self.max_stat_total.filter(|&max| total > max) {
+            violations.push(Violation::StatTotalTooHigh { member, total, max });
+        }
+
+        if let Some(max) = self.max_value {
+            let values = item.dragon.values();
+            let named = [
+                ("attack", values.attack),
+                ("defense", values.defense),
+                ("hp", values.hp),
+                ("speed", values.speed),
+            ];
+            for (stat, value) in named.iter().copied().filter(|&(_, v)| v > max) {
+                violations.push(Violation::ValueTooHigh {
+                    member,
+                    stat,
+                    value,
+                    max,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::num::NonZeroU32;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dragon::{Stat, Stats},
+    effect::{EffectContext, LongTermEffectTrait},
+    events::MoveEvent,
+    moves::MoveTrait,
+    party::RelativePartyId,
+};
+
+/// An item a dragon can hold in battle, as loaded from data.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ItemData {
+    pub name: String,
+    pub effects: Vec<ItemEffect>,
+}
+
+impl ItemData {
+    pub fn new(name: &str, effects: Vec<ItemEffect>) -> Self {
+        Self {
+            name: name.to_string(),
+            effects,
+        }
+    }
+}
+
+/// One part of what a held item does.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ItemEffect {
+    /// Multiplies a stat of the holder by `numerator / denominator`.
+    /// Only attack, defense and speed can be multiplied.
+    StatMultiplier {
+        stat: Stat,
+        numerator: u32,
+        denominator: NonZeroU32,
+    },
+    /// Heals `1 / denominator` of the maximum HP of the holder at
+    /// the end of each turn.
+    EndOfTurnHeal { denominator: NonZeroU32 },
+    /// Once the HP of the holder falls to `1 / threshold` of its maximum
+    /// HP or below, heals `1 / denominator` of it. The item is consumed.
+    HealAtThreshold {
+        threshold: NonZeroU32,
+        denominator: NonZeroU32,
+    },
+    /// Locks the holder into the first move it uses while holding the
+    /// item, until it is switched out.
+    ChoiceLock,
+}
+
+/// The effect of a held item on its holder. It is attached while the
+/// dragon holds the item, and can not be removed by cures.
+pub struct HeldItem {
+    data: ItemData,
+    locked_move: Option<String>,
+}
+
+impl HeldItem {
+    pub fn new(data: ItemData) -> Self {
+        Self {
+            data,
+            locked_move: None,
+        }
+    }
+
+    fn activated(&self, ctx: &mut EffectContext) {
+        ctx.emit(MoveEvent::ItemActivated {
+            rel_party_id: RelativePartyId::User,
+            item_name: self.data.name.clone(),
+        });
+    }
+
+    /// Activates the threshold healing of the item, if the
+    /// holder is low enough on HP.
+    fn check_threshold(&self, ctx: &mut EffectContext) {
+        let (hp, max_hp) = (ctx.user().hp, ctx.user().max_hp());
+        for effect in &self.data.effects {
+            if let ItemEffect::HealAtThreshold {
+                threshold,
+                denominator,
+            } = *effect
+            {
+                if hp > 0 && hp <= max_hp / threshold {
+                    self.activated(ctx);
+                    ctx.heal(RelativePartyId::User, max_hp / denominator);
+                    ctx.remove_item(RelativePartyId::User);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl LongTermEffectTrait for HeldItem {
+    fn get_name(&self) -> &str {
+        "held_item"
+    }
+
+    fn display_name(&self) -> &str {
+        &self.data.name
+    }
+
+    /// Held items act after the other effects at the end of the turn.
+    fn priority(&self) -> i8 {
+        -1
+    }
+
+    fn removable(&self) -> bool {
+        false
+    }
+
+    fn stat_modification(&self, stats: Stats) -> Stats {
+        self.data.effects.iter().fold(stats, |s, e| match *e {
+            ItemEffect::StatMultiplier {
+                stat,
+                numerator,
+                denominator,
+            } => match stat {
+                Stat::Attack => Stats {
+                    attack: s.attack.saturating_mul(numerator) / denominator,
+                    ..s
+                },
+                Stat::Defense => Stats {
+                    defense: s.defense.saturating_mul(numerator) / denominator,
+                    ..s
+                },
+                Stat::Speed => Stats {
+                    speed: s.speed.saturating_mul(numerator) / denominator,
+                    ..s
+                },
+                Stat::Accuracy | Stat::Evasion => s,
+            },
+            _ => s,
+        })
+    }
+
+    fn on_switch_in(&mut self, _ctx: &mut EffectContext) {
+        self.locked_move = None;
+    }
+
+    fn allows_move(&self, attack: &dyn MoveTrait) -> bool {
+        self.locked_move
+            .as_deref()
+            .is_none_or(|locked| locked == attack.get_name())
+    }
+
+    fn before_move(&mut self, ctx: &mut EffectContext, move_name: &str) -> bool {
+        match &self.locked_move {
+            Some(locked) if locked != move_name => {
+                ctx.emit(MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: format!("is locked into {}", locked),
+                });
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn after_move(&mut self, _ctx: &mut EffectContext, move_name: &str) {
+        let choice = self
+            .data
+            .effects
+            .iter()
+            .any(|e| matches!(e, ItemEffect::ChoiceLock));
+        if choice && self.locked_move.is_none() {
+            self.locked_move = Some(move_name.to_string());
+        }
+    }
+
+    fn on_damage(&self, ctx: &mut EffectContext, _amount: u32) {
+        self.check_threshold(ctx);
+    }
+
+    fn end_of_turn(&mut self, ctx: &mut EffectContext) {
+        for effect in &self.data.effects {
+            if let ItemEffect::EndOfTurnHeal { denominator } = *effect {
+                if ctx.user().hp < ctx.user().max_hp() {
+                    self.activated(ctx);
+                    ctx.heal(RelativePartyId::User, ctx.user().max_hp() / denominator);
+                }
+            }
+        }
+        self.check_threshold(ctx);
+    }
+}
@@ -1,35 +1,75 @@
+pub mod ability;
+pub mod bag;
 pub mod battle;
+pub mod damage;
 pub mod dragon;
 pub mod effect;
+pub mod encounter;
+pub mod error;
 pub mod events;
+pub mod format;
+pub mod item;
 pub use effect::effects;
 pub mod data;
 pub mod moves;
 pub mod party;
+pub mod side;
+pub mod team;
+pub mod types;
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        num::NonZeroU32,
+        sync::{Arc, Mutex},
+    };
+
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
     use crate::{
-        battle::{self, Battlefield},
-        dragon::{BattleDragon, Stats},
+        ability::{AbilityData, AbilityEffect},
+        bag::{BagItemData, BagItemEffect, BagRules},
+        battle::{self, Action, Battlefield},
+        damage::{
+            ClassicDamageCalculator, CritRules, DamageBreakdown, DamageCalculator, DamageInput,
+        },
+        data::Dex,
+        dragon::{
+            BattleDragon, DragonData, DragonIdentity, EvolutionCondition, EvolutionTrigger, Gender,
+            GrowthRate, Stat, StatOrder, Stats, MAX_VALUE,
+        },
+        effect::{EffectContext, LongTermEffectTrait, StatusCondition, VolatileCondition},
         effects,
-        moves::SimpleDamagingMove,
-        party::{Party, PartyId, PartyItem},
+        encounter::Encounter,
+        error::{EngineError, IllegalAction},
+        events::ProgressEvent,
+        format::{Format, Violation},
+        item::{HeldItem, ItemData, ItemEffect},
+        moves::{
+            AttackContext, AttackFlags, DamageKind, MoveStats, MoveTiming, SecondaryEffect,
+            SecondaryEffectKind, SemiInvulnerable, SimpleDamagingMove, StatusMove,
+            StatusMoveEffect,
+        },
+        party::{Party, PartyId, PartyItem, RelativePartyId},
+        side::{self, SideEffectData, SideEffects, WeatherData},
+        team,
+        types::TypeChart,
     };
 
     #[test]
     fn it_works() {
-        let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100)));
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100, 100)));
         mew.add_effect(Box::new(effects::AttackStageModifier::new(2)));
 
-        let mewtwo = PartyItem::new(BattleDragon::new(Stats::new_exact(110, 90, 106)));
+        let mewtwo = PartyItem::new(BattleDragon::new(Stats::new_exact(110, 90, 106, 130)));
 
-        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100)));
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100, 100)));
 
-        let mew_party = Party::new_from_vec(vec![mew, mewtwo]);
-        let opposing_party = Party::new_from_vec(vec![opposing_mew]);
+        let mew_party = Party::new_from_vec(vec![mew, mewtwo]).unwrap();
+        let opposing_party = Party::new_from_vec(vec![opposing_mew]).unwrap();
 
-        let mut battlefield = Battlefield::new(mew_party, opposing_party, battle::TestMessenger);
+        let mut battlefield =
+            Battlefield::new(mew_party, opposing_party, battle::TestMessenger).unwrap();
 
         battlefield.attack(
             PartyId::Party1,
@@ -40,7 +80,7 @@ mod tests {
             battlefield.party(PartyId::Party2).active().dragon.hp
         );
 
-        battlefield.party_mut(PartyId::Party1).switch(1);
+        battlefield.switch(PartyId::Party1, 1).unwrap();
         battlefield.attack(
             PartyId::Party1,
             &SimpleDamagingMove::new("Confusion".to_string(), 50),
@@ -50,4 +90,2083 @@ mod tests {
             battlefield.party(PartyId::Party2).active().dragon.hp
         );
     }
+
+    #[test]
+    fn side_effects_survive_switching() {
+        let mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        let mewtwo = PartyItem::new(BattleDragon::new(Stats::new_exact(110, 90, 95, 130)));
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew, mewtwo]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        battlefield.side_mut(PartyId::Party1).add_effect(Box::new(
+            side::effects::EntryHazard::new(NonZeroU32::new(8).unwrap()),
+        ));
+        battlefield
+            .side_mut(PartyId::Party1)
+            .add_effect(Box::new(side::effects::Tailwind::new(2)));
+
+        battlefield.switch(PartyId::Party1, 1).unwrap();
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().hp(),
+            100 - 100 / 8
+        );
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).speed, 260);
+
+        battlefield.turn();
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).speed, 260);
+        battlefield.turn();
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).speed, 130);
+        assert!(battlefield.side(PartyId::Party1).contains("entry_hazard"));
+    }
+
+    /// Hurts the attacker when hit, and heals a bit at the end of each turn.
+    struct Spiky;
+    impl LongTermEffectTrait for Spiky {
+        fn get_name(&self) -> &str {
+            "spiky"
+        }
+
+        fn on_hit(&self, ctx: &mut EffectContext, _move_name: &str) {
+            let amount = ctx.opponent().unwrap().dragon.max_hp() / 8;
+            ctx.damage(RelativePartyId::Opposing, amount);
+        }
+
+        fn end_of_turn(&mut self, ctx: &mut EffectContext) {
+            ctx.heal(RelativePartyId::User, 5);
+        }
+    }
+
+    #[test]
+    fn effect_hooks_act_through_context() {
+        let mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        let mut opposing_mew =
+            PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        opposing_mew.add_effect(Box::new(Spiky));
+
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Pound".to_string(), 40),
+        );
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().hp(),
+            100 - 100 / 8
+        );
+
+        let damaged_hp = battlefield.party(PartyId::Party2).active().hp();
+        assert!(damaged_hp < 100);
+        battlefield.turn();
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().hp(),
+            damaged_hp + 5
+        );
+    }
+
+    /// Counts the times the dragon faints.
+    struct FaintCounter(Arc<Mutex<u32>>);
+    impl LongTermEffectTrait for FaintCounter {
+        fn get_name(&self) -> &str {
+            "faint_counter"
+        }
+
+        fn on_faint(&self, _ctx: &mut EffectContext, fainted: RelativePartyId) {
+            if fainted == RelativePartyId::User {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn attackers_faint_once() {
+        let faints = Arc::new(Mutex::new(0));
+        let new_battlefield = || {
+            let mut mew = PartyItem::new(BattleDragon::new(Stats::new()));
+            mew.add_effect(Box::new(FaintCounter(faints.clone())));
+            mew.damage(100);
+            let mut opposing_mew = PartyItem::new(BattleDragon::new(Stats::new()));
+            opposing_mew.add_effect(Box::new(Spiky));
+            Battlefield::new(
+                Party::new_from_vec(vec![mew]).unwrap(),
+                Party::new_from_vec(vec![opposing_mew]).unwrap(),
+                battle::TestMessenger,
+            )
+            .unwrap()
+        };
+
+        let mut battlefield = new_battlefield();
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Pound".to_string(), 40),
+        );
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 0);
+        assert_eq!(*faints.lock().unwrap(), 1);
+
+        let mut battlefield = new_battlefield();
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Take Down".to_string(), 0)
+                .with_damage_kind(DamageKind::Fixed(20))
+                .with_recoil(1, NonZeroU32::new(2).unwrap()),
+        );
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 0);
+        assert_eq!(*faints.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn on_hit_only_reacts_to_moves_reaching_the_dragon() {
+        let mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        let mut opposing_mew =
+            PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        opposing_mew.add_effect(Box::new(Spiky));
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        let stage = |target, amount| {
+            StatusMove::new(
+                "Stage",
+                vec![StatusMoveEffect::Stage {
+                    target,
+                    stat: Stat::Attack,
+                    amount,
+                }],
+            )
+        };
+
+        battlefield.attack(PartyId::Party1, &stage(RelativePartyId::User, 1));
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Solar Beam".to_string(), 120).with_timing(
+                MoveTiming::Charge {
+                    message: "took in sunlight".to_string(),
+                    position: None,
+                },
+            ),
+        );
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 100);
+
+        battlefield.attack(PartyId::Party1, &stage(RelativePartyId::Opposing, -1));
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().hp(),
+            100 - 100 / 8
+        );
+    }
+
+    /// Records a roll each time it is added to a dragon.
+    struct Dice(Arc<Mutex<Vec<u32>>>);
+    impl LongTermEffectTrait for Dice {
+        fn get_name(&self) -> &str {
+            "dice"
+        }
+
+        fn apply(&self, ctx: &mut EffectContext) -> bool {
+            let roll = ctx.rng().next_u32();
+            self.0.lock().unwrap().push(roll);
+            false
+        }
+    }
+
+    #[test]
+    fn effects_added_outside_battle_roll_the_same() {
+        let rolls = Arc::new(Mutex::new(vec![]));
+        for _ in 0..2 {
+            let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+            mew.add_effect(Box::new(Dice(rolls.clone())));
+        }
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        mew.add_effect_with_rng(Box::new(Dice(rolls.clone())), &mut StdRng::seed_from_u64(3));
+        let rolls = rolls.lock().unwrap();
+        assert_eq!(rolls[0], rolls[1]);
+        assert_eq!(rolls[2], StdRng::seed_from_u64(3).next_u32());
+    }
+
+    #[test]
+    fn poison_hurts_at_end_of_turn() {
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+        mew.add_effect(Box::new(effects::Poison::new(NonZeroU32::new(8).unwrap())));
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 100)));
+
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            7,
+        )
+        .unwrap();
+        battlefield.turn();
+        battlefield.turn();
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().hp(),
+            100 - 2 * 12
+        );
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 100);
+    }
+
+    /// Records its label at the end of each turn.
+    struct Recorder(&'static str, i8, Arc<Mutex<Vec<&'static str>>>);
+    impl LongTermEffectTrait for Recorder {
+        fn get_name(&self) -> &str {
+            "recorder"
+        }
+
+        fn priority(&self) -> i8 {
+            self.1
+        }
+
+        fn end_of_turn(&mut self, _ctx: &mut EffectContext) {
+            self.2.lock().unwrap().push(self.0);
+        }
+    }
+
+    #[test]
+    fn end_of_turn_order_is_priority_then_speed() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut slow = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 50)));
+        slow.add_effect(Box::new(Recorder("slow low", 0, log.clone())));
+        slow.add_effect(Box::new(Recorder("slow high", 1, log.clone())));
+        let mut fast = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 150)));
+        fast.add_effect(Box::new(Recorder("fast low", 0, log.clone())));
+
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![slow]).unwrap(),
+            Party::new_from_vec(vec![fast]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        battlefield.turn();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["slow high", "fast low", "slow low"]
+        );
+    }
+
+    /// Records the attack it sees, then changes it in a recognizable way.
+    struct AttackInspector {
+        views: Arc<Mutex<Vec<AttackContext>>>,
+        veto: bool,
+    }
+    impl LongTermEffectTrait for AttackInspector {
+        fn get_name(&self) -> &str {
+            "attack_inspector"
+        }
+
+        fn offending(&self, attack: AttackContext) -> Option<AttackContext> {
+            self.views.lock().unwrap().push(attack.clone());
+            let mut move_stats = attack.move_stats.clone();
+            move_stats.base_power *= 2;
+            Some(AttackContext {
+                move_stats,
+                ..attack
+            })
+        }
+
+        fn defending(&self, attack: AttackContext) -> Option<AttackContext> {
+            self.views.lock().unwrap().push(attack.clone());
+            if self.veto {
+                return None;
+            }
+            let mut defender_stages = attack.defender_stages;
+            defender_stages.defense += 1;
+            Some(AttackContext {
+                defender_stages,
+                ..attack
+            })
+        }
+    }
+
+    fn inspected_pair(
+        views: &Arc<Mutex<Vec<AttackContext>>>,
+        veto: bool,
+    ) -> (PartyItem, PartyItem) {
+        let mut attacker = PartyItem::new(BattleDragon::new(Stats::new()));
+        attacker.add_effect(Box::new(effects::AttackStageModifier::new(2)));
+        attacker.add_effect(Box::new(AttackInspector {
+            views: views.clone(),
+            veto: false,
+        }));
+        let mut defender = PartyItem::new(BattleDragon::new(Stats::new()));
+        defender.add_effect(Box::new(effects::DefenseStageModifier::new(1)));
+        defender.add_effect(Box::new(AttackInspector {
+            views: views.clone(),
+            veto,
+        }));
+        (attacker, defender)
+    }
+
+    #[test]
+    fn offending_sees_both_dragons_stages() {
+        let views = Arc::new(Mutex::new(vec![]));
+        let (attacker, defender) = inspected_pair(&views, false);
+        attacker.offend(MoveStats::new(40, 100), AttackFlags::default(), &defender);
+
+        let offending = views.lock().unwrap()[0].clone();
+        assert_eq!(offending.attacker_stages.attack, 2);
+        assert_eq!(offending.attacker_stages.defense, 0);
+        assert_eq!(offending.defender_stages.attack, 0);
+        assert_eq!(offending.defender_stages.defense, 1);
+        assert_eq!(offending.move_stats.base_power, 40);
+    }
+
+    #[test]
+    fn defending_sees_offending_changes() {
+        let views = Arc::new(Mutex::new(vec![]));
+        let (attacker, defender) = inspected_pair(&views, false);
+        let flags = AttackFlags {
+            contact: true,
+            ..AttackFlags::default()
+        };
+        attacker.offend(MoveStats::new(40, 100), flags, &defender);
+
+        let defending = views.lock().unwrap()[1].clone();
+        assert_eq!(defending.attacker_stages.attack, 2);
+        assert_eq!(defending.defender_stages.defense, 1);
+        assert_eq!(defending.move_stats.base_power, 80);
+        assert!(defending.flags.contact);
+    }
+
+    #[test]
+    fn offend_returns_final_attack_unswapped() {
+        let views = Arc::new(Mutex::new(vec![]));
+        let (attacker, defender) = inspected_pair(&views, false);
+        let attack = attacker
+            .offend(MoveStats::new(40, 100), AttackFlags::default(), &defender)
+            .unwrap();
+
+        assert_eq!(attack.attacker_stages.attack, 2);
+        assert_eq!(attack.attacker_stages.defense, 0);
+        assert_eq!(attack.defender_stages.attack, 0);
+        assert_eq!(attack.defender_stages.defense, 2);
+        assert_eq!(attack.move_stats.base_power, 80);
+    }
+
+    #[test]
+    fn defending_can_stop_attack() {
+        let views = Arc::new(Mutex::new(vec![]));
+        let (attacker, defender) = inspected_pair(&views, true);
+        let attack = attacker.offend(MoveStats::new(40, 100), AttackFlags::default(), &defender);
+
+        assert!(attack.is_none());
+        assert_eq!(views.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn abilities_attach_on_entry_and_survive_cures() {
+        let intimidate = AbilityData::new(
+            "Intimidate",
+            vec![
+                AbilityEffect::OpponentStageOnEntry {
+                    stat: Stat::Attack,
+                    amount: -1,
+                },
+                AbilityEffect::EffectImmunity {
+                    effects: vec!["poison".to_string()],
+                },
+                AbilityEffect::DefendingPowerMultiplier {
+                    numerator: 3,
+                    denominator: NonZeroU32::new(2).unwrap(),
+                },
+            ],
+        );
+        let mew = PartyItem::new(BattleDragon::new(Stats::new())).with_ability(intimidate);
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new()));
+
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        let opponent = battlefield.party(PartyId::Party2).active();
+        assert_eq!(opponent.calc_stages().attack, -1);
+        let mew = battlefield.party(PartyId::Party1).active();
+        let attack = opponent
+            .offend(MoveStats::new(u32::MAX, 100), AttackFlags::default(), mew)
+            .unwrap();
+        assert_eq!(attack.move_stats.base_power, u32::MAX / 2);
+
+        let mew = battlefield.party_mut(PartyId::Party1).active_mut();
+        mew.add_effect(Box::new(effects::Poison::new(NonZeroU32::new(8).unwrap())));
+        assert!(!mew.has_effect("poison"));
+        mew.clear_effects();
+        assert!(!mew.remove_effect("ability"));
+        assert!(mew.has_effect("ability"));
+    }
+
+    #[test]
+    fn held_items_activate_and_can_be_stolen() {
+        let berry = ItemData::new(
+            "Berry",
+            vec![ItemEffect::HealAtThreshold {
+                threshold: NonZeroU32::new(2).unwrap(),
+                denominator: NonZeroU32::new(4).unwrap(),
+            }],
+        );
+        let scarf = ItemData::new(
+            "Scarf",
+            vec![
+                ItemEffect::StatMultiplier {
+                    stat: Stat::Speed,
+                    numerator: 3,
+                    denominator: NonZeroU32::new(2).unwrap(),
+                },
+                ItemEffect::ChoiceLock,
+            ],
+        );
+        let fastest = Stats::new_exact(0, 0, 0, u32::MAX);
+        assert_eq!(
+            HeldItem::new(scarf.clone())
+                .stat_modification(fastest)
+                .speed,
+            u32::MAX / 2
+        );
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new())).with_item(scarf);
+        mew.add_effect(Box::new(effects::AttackStageModifier::new(6)));
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new())).with_item(berry);
+
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).speed, 150);
+
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Pound".to_string(), 40),
+        );
+        let opponent = battlefield.party(PartyId::Party2).active();
+        assert!(opponent.item().is_none());
+        let hp_after_berry = opponent.hp();
+
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Tackle".to_string(), 40),
+        );
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().hp(),
+            hp_after_berry
+        );
+
+        assert!(battlefield.steal_item(PartyId::Party2));
+        assert_eq!(
+            battlefield
+                .party(PartyId::Party2)
+                .active()
+                .item()
+                .unwrap()
+                .name,
+            "Scarf"
+        );
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).speed, 100);
+    }
+
+    #[test]
+    fn bag_items_are_used_before_moves() {
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 50)))
+            .with_move(
+                Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+                35,
+            );
+        mew.add_effect(Box::new(effects::Poison::new(NonZeroU32::new(8).unwrap())));
+        mew.damage(50);
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 150)))
+            .with_move(
+                Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+                35,
+            );
+
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            11,
+        )
+        .unwrap();
+        let potion = BagItemData::new(
+            "Potion",
+            vec![
+                BagItemEffect::Heal { amount: 20 },
+                BagItemEffect::CureStatus,
+            ],
+        );
+        battlefield.inventory_mut(PartyId::Party1).add(potion, 1);
+
+        battlefield.set_bag_rules(BagRules::Forbidden);
+        let use_potion = Action::UseItem {
+            item: "Potion".to_string(),
+            target: 0,
+        };
+        assert!(battlefield
+            .submit_action(PartyId::Party1, use_potion.clone())
+            .is_err());
+        battlefield.set_bag_rules(BagRules::Only(vec!["Potion".to_string()]));
+        battlefield
+            .submit_action(PartyId::Party1, use_potion)
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert!(!mew.has_effect("poison"));
+        assert!(mew.hp() < 75);
+        assert_eq!(battlefield.inventory(PartyId::Party1).count("Potion"), 0);
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().moves()[0].pp,
+            34
+        );
+
+        let revive = BagItemData::new(
+            "Revive",
+            vec![BagItemEffect::Revive {
+                denominator: NonZeroU32::new(2).unwrap(),
+            }],
+        );
+        let mut fainted = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 95, 50)));
+        fainted.damage(100);
+        assert!(revive.use_on(&mut fainted));
+        assert_eq!(fainted.hp(), 50);
+    }
+
+    #[test]
+    fn crit_rules_are_configurable() {
+        let mew = PartyItem::new(BattleDragon::new(Stats::new()));
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new()));
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        battlefield.set_crit_rules(CritRules {
+            stage_table: vec![0, 1],
+            ..CritRules::default()
+        });
+        battlefield.set_damage_calculator(Box::new(ClassicDamageCalculator {
+            min_roll: 100,
+            ..ClassicDamageCalculator::default()
+        }));
+        battlefield
+            .side_mut(PartyId::Party2)
+            .add_effect(Box::new(side::effects::Screen::new(5)));
+        let pound = SimpleDamagingMove::new("Pound".to_string(), 40);
+
+        battlefield.attack(PartyId::Party1, &pound);
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().hp(),
+            105 - 19 / 2
+        );
+
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .add_effect(Box::new(effects::CritStageModifier::new(1)));
+        battlefield.attack(PartyId::Party1, &pound);
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().hp(),
+            105 - 19 / 2 - 19 * 3 / 2
+        );
+    }
+
+    #[test]
+    fn crits_ignore_screens_but_not_weather() {
+        let attacker = PartyItem::new(BattleDragon::new(Stats::new()));
+        let defender = PartyItem::new(BattleDragon::new(Stats::new()));
+        let attack = attacker
+            .offend(
+                MoveStats::new(40, 100).with_type("Fire"),
+                AttackFlags::default(),
+                &defender,
+            )
+            .unwrap();
+        let sun = || {
+            Box::new(side::effects::Weather::new(WeatherData {
+                name: "sun".to_string(),
+                turns: 5,
+                boosted_type: Some("Fire".to_string()),
+                weakened_type: None,
+            }))
+        };
+        let mut sunny = SideEffects::new();
+        sunny.add_effect(sun());
+        let mut screened = SideEffects::new();
+        screened.add_effect(sun());
+        screened.add_effect(Box::new(side::effects::Screen::new(5)));
+        let damage = |side: &SideEffects, critical| {
+            ClassicDamageCalculator {
+                min_roll: 100,
+                ..ClassicDamageCalculator::default()
+            }
+            .calculate(&DamageInput {
+                attacker: &attacker,
+                defender: &defender,
+                defender_side: side,
+                attack: &attack,
+                crit_rules: &CritRules::default(),
+                critical,
+                roll: 100,
+            })
+            .damage
+        };
+
+        assert_eq!(damage(&SideEffects::new(), true), 28);
+        assert_eq!(damage(&sunny, false), 28);
+        assert_eq!(damage(&sunny, true), 42);
+        assert_eq!(damage(&screened, false), 14);
+        assert_eq!(damage(&screened, true), 42);
+    }
+
+    #[test]
+    fn damage_calculator_applies_stab_and_types() {
+        let mut chart = TypeChart::new();
+        chart.set("Fire", "Grass", 2.);
+        chart.set("Fire", "Water", 0.5);
+        let calculator = ClassicDamageCalculator {
+            min_roll: 100,
+            ..ClassicDamageCalculator::new(chart)
+        };
+        let attacker = PartyItem::new(BattleDragon::new(Stats::new()).with_types(&["Fire"]));
+        let defender =
+            PartyItem::new(BattleDragon::new(Stats::new()).with_types(&["Grass", "Water"]));
+        let attack = attacker
+            .offend(
+                MoveStats::new(40, 100).with_type("Fire"),
+                AttackFlags::default(),
+                &defender,
+            )
+            .unwrap();
+        let breakdown = calculator.calculate(&DamageInput {
+            attacker: &attacker,
+            defender: &defender,
+            defender_side: &SideEffects::new(),
+            attack: &attack,
+            crit_rules: &CritRules::default(),
+            critical: false,
+            roll: 100,
+        });
+
+        assert_eq!(breakdown.base_damage, 19);
+        assert_eq!(breakdown.stab, 1.5);
+        assert_eq!(breakdown.type_effectiveness, 1.);
+        assert_eq!(breakdown.damage, 19 * 3 / 2);
+
+        let weak = PartyItem::new(BattleDragon::new(Stats::new()).with_types(&["Grass"]));
+        let breakdown = calculator.calculate(&DamageInput {
+            attacker: &attacker,
+            defender: &weak,
+            defender_side: &SideEffects::new(),
+            attack: &attack,
+            crit_rules: &CritRules::default(),
+            critical: false,
+            roll: 85,
+        });
+        assert_eq!(breakdown.type_effectiveness, 2.);
+        assert_eq!(breakdown.damage, 19 * 85 / 100 * 3 / 2 * 2);
+    }
+
+    /// Deals the base power of every move as damage.
+    struct FlatCalculator;
+    impl DamageCalculator for FlatCalculator {
+        fn calculate(&self, input: &DamageInput) -> DamageBreakdown {
+            let base_power = input.attack.move_stats.base_power;
+            DamageBreakdown {
+                level: input.attacker.dragon.level(),
+                attack: 0,
+                defense: 0,
+                base_power,
+                base_damage: base_power,
+                roll: input.roll,
+                stab: 1.,
+                type_effectiveness: 1.,
+                critical: input.critical,
+                crit_multiplier: 1.,
+                modifier: 1.,
+                damage_before_side: base_power,
+                damage: base_power,
+            }
+        }
+    }
+
+    #[test]
+    fn damage_calculator_can_be_replaced() {
+        let mut battlefield = Battlefield::new(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+        battlefield.set_damage_calculator(Box::new(FlatCalculator));
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Pound".to_string(), 40),
+        );
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105 - 40);
+    }
+
+    #[test]
+    fn damage_preview_covers_every_roll() {
+        let mew = PartyItem::new(BattleDragon::new(Stats::new())).with_move(
+            Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+            35,
+        );
+        let mut opposing_mew = PartyItem::new(BattleDragon::new(Stats::new()));
+        opposing_mew.damage(87);
+        let battlefield = Battlefield::new(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+        )
+        .unwrap();
+
+        let preview = battlefield.preview_damage(PartyId::Party1, 0).unwrap();
+        assert_eq!((preview.normal.min, preview.normal.max), (16, 19));
+        assert_eq!(preview.normal.max_percent, 19. * 100. / 105.);
+        assert_eq!(preview.normal.ko_chance, 6. / 16.);
+        assert_eq!(preview.normal.two_hit_ko_chance, 1.);
+        assert_eq!((preview.critical.min, preview.critical.max), (24, 28));
+        assert_eq!(preview.critical.ko_chance, 1.);
+        assert_eq!(preview.crit_chance, 1. / 24.);
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 18);
+        assert!(battlefield.preview_damage(PartyId::Party1, 1).is_none());
+    }
+
+    #[test]
+    fn secondary_effects_trigger_after_hits() {
+        let fire_fang = SimpleDamagingMove::new("Fire Fang".to_string(), 65)
+            .with_secondary_effect(SecondaryEffect::new(
+                100,
+                RelativePartyId::Opposing,
+                SecondaryEffectKind::Status(StatusCondition::Burn),
+            ))
+            .with_secondary_effect(SecondaryEffect::new(
+                100,
+                RelativePartyId::Opposing,
+                SecondaryEffectKind::Flinch,
+            ))
+            .with_secondary_effect(SecondaryEffect::new(
+                0,
+                RelativePartyId::User,
+                SecondaryEffectKind::Stage {
+                    stat: Stat::Attack,
+                    amount: 1,
+                },
+            ));
+        let mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100, 150)))
+            .with_move(Arc::new(fire_fang), 15);
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new())).with_move(
+            Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+            35,
+        );
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            7,
+        )
+        .unwrap();
+
+        battlefield
+            .submit_action(PartyId::Party1, Action::Move(0))
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert_eq!(mew.hp(), 105);
+        assert!(!mew.has_effect("stat_modifier"));
+        let opposing_mew = battlefield.party(PartyId::Party2).active();
+        assert!(opposing_mew.has_effect("burn"));
+        assert!(!opposing_mew.has_effect("flinch"));
+        assert_eq!(opposing_mew.calc_stats().attack, 50);
+
+        battlefield.attack(
+            PartyId::Party1,
+            &SimpleDamagingMove::new("Ember".to_string(), 40).with_secondary_effect(
+                SecondaryEffect::new(
+                    100,
+                    RelativePartyId::Opposing,
+                    SecondaryEffectKind::Status(StatusCondition::Paralysis),
+                ),
+            ),
+        );
+        let opposing_mew = battlefield.party(PartyId::Party2).active();
+        assert!(!opposing_mew.has_effect("paralysis"));
+    }
+
+    #[test]
+    fn stat_stages_are_capped_and_merged() {
+        let mut mew = PartyItem::new(BattleDragon::new(Stats::new()));
+        for _ in 0..30 {
+            mew.add_effect(Box::new(effects::StatStageModifier::new(Stat::Attack, 6)));
+        }
+        mew.add_effect(Box::new(effects::AttackStageModifier::new(1)));
+        assert_eq!(mew.calc_stages().attack, 6);
+        assert_eq!(mew.calc_stats().attack, 400);
+        assert_eq!(mew.effects.len(), 1);
+
+        mew.add_effect(Box::new(effects::AttackStageModifier::new(-8)));
+        mew.add_effect(Box::new(effects::DefenseStageModifier::new(-8)));
+        assert_eq!(mew.calc_stages().attack, -2);
+        assert_eq!(mew.calc_stages().defense, -6);
+        assert_eq!(mew.calc_stats().defense, 25);
+        assert_eq!(mew.effects.len(), 2);
+    }
+
+    #[test]
+    fn status_moves_apply_their_effects() {
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        let growl = StatusMove::new(
+            "Growl",
+            vec![StatusMoveEffect::Stage {
+                target: RelativePartyId::Opposing,
+                stat: Stat::Attack,
+                amount: -1,
+            }],
+        )
+        .with_accuracy(100);
+        let protect = StatusMove::new("Protect", vec![StatusMoveEffect::Protect]);
+
+        battlefield.attack(PartyId::Party2, &protect);
+        battlefield.attack(PartyId::Party1, &growl);
+        assert!(!battlefield
+            .party(PartyId::Party2)
+            .active()
+            .has_effect("stat_modifier"));
+        battlefield.turn();
+        battlefield.attack(PartyId::Party1, &growl);
+        assert_eq!(battlefield.calc_stats(PartyId::Party2).attack, 66);
+
+        let never_hits = StatusMove::new(
+            "Never Hits",
+            vec![StatusMoveEffect::Status(StatusCondition::Sleep)],
+        )
+        .with_accuracy(0);
+        battlefield.attack(PartyId::Party1, &never_hits);
+        assert!(!battlefield.party(PartyId::Party2).active().has_status());
+
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .damage(60);
+        battlefield.attack(
+            PartyId::Party1,
+            &StatusMove::new("Recover", vec![StatusMoveEffect::Heal { percent: 50 }]),
+        );
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 45 + 52);
+
+        battlefield.attack(
+            PartyId::Party1,
+            &StatusMove::new(
+                "Spikes",
+                vec![StatusMoveEffect::SideEffect {
+                    target: RelativePartyId::Opposing,
+                    effect: SideEffectData::EntryHazard {
+                        denominator: NonZeroU32::new(8).unwrap(),
+                    },
+                }],
+            ),
+        );
+        assert!(battlefield.side(PartyId::Party2).contains("entry_hazard"));
+        assert!(!battlefield.side(PartyId::Party1).contains("entry_hazard"));
+
+        battlefield.attack(
+            PartyId::Party1,
+            &StatusMove::new(
+                "Rain Dance",
+                vec![StatusMoveEffect::Weather(WeatherData {
+                    name: "rain".to_string(),
+                    turns: 5,
+                    boosted_type: Some("Water".to_string()),
+                    weakened_type: Some("Fire".to_string()),
+                })],
+            ),
+        );
+        assert!(battlefield.side(PartyId::Party1).contains("weather"));
+        assert_eq!(battlefield.side(PartyId::Party2).count("weather"), 1);
+    }
+
+    #[test]
+    fn damage_kinds_compose() {
+        let new_battlefield = |level| {
+            Battlefield::new_seeded(
+                Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+                Party::new_from_vec(vec![PartyItem::new(
+                    BattleDragon::new(Stats::new()).with_level(level),
+                )])
+                .unwrap(),
+                battle::TestMessenger,
+                11,
+            )
+            .unwrap()
+        };
+        let hp = |battlefield: &Battlefield<battle::TestMessenger>, id| {
+            battlefield.party(id).active().hp()
+        };
+
+        let mut battlefield = new_battlefield(50);
+        let double_kick = SimpleDamagingMove::new("Double Kick".to_string(), 0)
+            .with_damage_kind(DamageKind::Fixed(10))
+            .with_hits(2, 5);
+        battlefield.attack(PartyId::Party1, &double_kick);
+        let dealt = 105 - hp(&battlefield, PartyId::Party2);
+        assert!((20..=50).contains(&dealt) && dealt % 10 == 0);
+
+        let mut battlefield = new_battlefield(50);
+        let take_down = SimpleDamagingMove::new("Take Down".to_string(), 0)
+            .with_damage_kind(DamageKind::Level)
+            .with_recoil(1, NonZeroU32::new(5).unwrap());
+        battlefield.attack(PartyId::Party1, &take_down);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 105 - 50);
+        assert_eq!(hp(&battlefield, PartyId::Party1), 105 - 10);
+
+        let absorb = SimpleDamagingMove::new("Absorb".to_string(), 0)
+            .with_damage_kind(DamageKind::CurrentHpPercent(50))
+            .with_drain(1, NonZeroU32::new(2).unwrap());
+        battlefield.attack(PartyId::Party1, &absorb);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 55 - 27);
+        assert_eq!(hp(&battlefield, PartyId::Party1), 95 + 10);
+
+        let mut battlefield = new_battlefield(50);
+        let graze = SimpleDamagingMove::new("Graze".to_string(), 0)
+            .with_damage_kind(DamageKind::Fixed(0))
+            .with_recoil(1, NonZeroU32::new(4).unwrap())
+            .with_drain(1, NonZeroU32::new(2).unwrap());
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .damage(5);
+        battlefield.attack(PartyId::Party1, &graze);
+        assert_eq!(hp(&battlefield, PartyId::Party1), 100);
+
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new_exact(
+                100,
+                100,
+                4_000_000_000,
+                100,
+            )))])
+            .unwrap(),
+            battle::TestMessenger,
+            11,
+        )
+        .unwrap();
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .damage(50);
+        let giga_drain = SimpleDamagingMove::new("Giga Drain".to_string(), 0)
+            .with_damage_kind(DamageKind::CurrentHpPercent(50))
+            .with_drain(u32::MAX, NonZeroU32::new(1).unwrap());
+        battlefield.attack(PartyId::Party1, &giga_drain);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 2_000_000_003);
+        assert_eq!(hp(&battlefield, PartyId::Party1), 105);
+
+        let fissure = SimpleDamagingMove::new("Fissure".to_string(), 0)
+            .with_damage_kind(DamageKind::OneHitKo);
+        let mut battlefield = new_battlefield(51);
+        battlefield.attack(PartyId::Party1, &fissure);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 105);
+        let mut battlefield = new_battlefield(50);
+        let missing_fissure = SimpleDamagingMove::new("Fissure".to_string(), 0)
+            .with_damage_kind(DamageKind::OneHitKo)
+            .with_accuracy(0);
+        battlefield.attack(PartyId::Party1, &missing_fissure);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 105);
+        battlefield.attack(PartyId::Party1, &fissure);
+        assert_eq!(hp(&battlefield, PartyId::Party2), 0);
+    }
+
+    fn multi_turn_battlefield(timing: MoveTiming) -> Battlefield<battle::TestMessenger> {
+        let strike = SimpleDamagingMove::new("Strike".to_string(), 40).with_timing(timing);
+        let mew = PartyItem::new(BattleDragon::new(Stats::new_exact(100, 100, 100, 150)))
+            .with_move(Arc::new(strike), 10)
+            .with_move(
+                Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+                35,
+            );
+        let opposing_mew = PartyItem::new(BattleDragon::new(Stats::new())).with_move(
+            Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+            35,
+        );
+        Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            5,
+        )
+        .unwrap()
+    }
+
+    fn run_moves(battlefield: &mut Battlefield<battle::TestMessenger>, slot: usize) {
+        battlefield
+            .submit_action(PartyId::Party1, Action::Move(slot))
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+    }
+
+    #[test]
+    fn charging_moves_hit_in_the_next_turn() {
+        let mut battlefield = multi_turn_battlefield(MoveTiming::Charge {
+            message: "flew up high".to_string(),
+            position: Some(SemiInvulnerable::Air),
+        });
+        run_moves(&mut battlefield, 0);
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 105);
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105);
+
+        run_moves(&mut battlefield, 1);
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert!(mew.hp() < 105);
+        assert_eq!(mew.moves()[0].pp, 9);
+        assert_eq!(mew.moves()[1].pp, 35);
+        assert!(battlefield.party(PartyId::Party2).active().hp() < 105);
+    }
+
+    #[test]
+    fn charges_are_lost_when_the_strike_is_skipped() {
+        let mut battlefield = multi_turn_battlefield(MoveTiming::Charge {
+            message: "flew up high".to_string(),
+            position: Some(SemiInvulnerable::Air),
+        });
+        run_moves(&mut battlefield, 0);
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .add_effect(Box::new(effects::Sleep::new(2)));
+        run_moves(&mut battlefield, 0);
+
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert!(!mew.has_effect("charging"));
+        assert_eq!(mew.forced_move(), None);
+        assert!(mew.hp() < 105);
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105);
+
+        let mut battlefield = multi_turn_battlefield(MoveTiming::Rampage { min: 3, max: 3 });
+        run_moves(&mut battlefield, 0);
+        let fissure = SimpleDamagingMove::new("Fissure".to_string(), 0)
+            .with_damage_kind(DamageKind::OneHitKo);
+        battlefield.attack(PartyId::Party2, &fissure);
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert_eq!(mew.hp(), 0);
+        assert!(!mew.has_effect("rampage"));
+    }
+
+    #[test]
+    fn recharging_moves_skip_the_next_turn() {
+        let mut battlefield = multi_turn_battlefield(MoveTiming::Recharge);
+        run_moves(&mut battlefield, 0);
+        let hp = battlefield.party(PartyId::Party2).active().hp();
+        assert!(hp < 105);
+
+        run_moves(&mut battlefield, 1);
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), hp);
+        run_moves(&mut battlefield, 1);
+        assert!(battlefield.party(PartyId::Party2).active().hp() < hp);
+    }
+
+    #[test]
+    fn rampage_locks_in_then_confuses() {
+        let mut battlefield = multi_turn_battlefield(MoveTiming::Rampage { min: 2, max: 2 });
+        run_moves(&mut battlefield, 0);
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().forced_move(),
+            Some(0)
+        );
+        run_moves(&mut battlefield, 1);
+
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert_eq!(mew.forced_move(), None);
+        assert!(mew.has_effect("confusion"));
+        assert_eq!(mew.moves()[0].pp, 9);
+        assert_eq!(mew.moves()[1].pp, 35);
+    }
+
+    #[test]
+    fn protect_fails_more_often_in_a_row() {
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            battle::TestMessenger,
+            9,
+        )
+        .unwrap();
+        let protect = StatusMove::new("Protect", vec![StatusMoveEffect::Protect]);
+        let mut results = vec![];
+        for _ in 0..20 {
+            battlefield.attack(PartyId::Party1, &protect);
+            results.push(
+                battlefield
+                    .party(PartyId::Party1)
+                    .active()
+                    .has_effect("protect"),
+            );
+            battlefield.turn();
+        }
+        assert!(results[0]);
+        assert!(results.contains(&false));
+        for pair in results.windows(2) {
+            assert!(pair[0] || pair[1]);
+        }
+    }
+
+    #[test]
+    fn substitute_takes_hits_and_blocks_status() {
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            battle::TestMessenger,
+            13,
+        )
+        .unwrap();
+        let substitute = StatusMove::new("Substitute", vec![StatusMoveEffect::Substitute]);
+        battlefield.attack(PartyId::Party1, &substitute);
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 105 - 26);
+        battlefield.attack(PartyId::Party1, &substitute);
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 105 - 26);
+
+        let hypnosis = StatusMove::new(
+            "Hypnosis",
+            vec![StatusMoveEffect::Status(StatusCondition::Sleep)],
+        );
+        battlefield.attack(PartyId::Party2, &hypnosis);
+        assert!(!battlefield.party(PartyId::Party1).active().has_status());
+
+        let hit = |amount| {
+            SimpleDamagingMove::new("Hit".to_string(), 0)
+                .with_damage_kind(DamageKind::Fixed(amount))
+        };
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .add_effect(Box::new(Spiky));
+        let ember = hit(5).with_secondary_effect(SecondaryEffect::new(
+            100,
+            RelativePartyId::Opposing,
+            SecondaryEffectKind::Status(StatusCondition::Burn),
+        ));
+        battlefield.attack(PartyId::Party2, &ember);
+        assert!(!battlefield.party(PartyId::Party1).active().has_status());
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105);
+
+        battlefield.attack(PartyId::Party2, &hit(10));
+        battlefield.attack(PartyId::Party2, &hit(30));
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 105 - 26);
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105);
+        battlefield.attack(PartyId::Party2, &hit(10));
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), 105 - 36);
+        assert_eq!(
+            battlefield.party(PartyId::Party2).active().hp(),
+            105 - 105 / 8
+        );
+    }
+
+    #[test]
+    fn redirection_decides_who_is_hit() {
+        let mut opposing_mew = PartyItem::new(BattleDragon::new(Stats::new()));
+        opposing_mew.add_effect(Box::new(Spiky));
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            Party::new_from_vec(vec![opposing_mew]).unwrap(),
+            battle::TestMessenger,
+            17,
+        )
+        .unwrap();
+        let follow_me =
+            |slot| StatusMove::new("Follow Me", vec![StatusMoveEffect::Redirect { slot }]);
+        battlefield.attack(PartyId::Party2, &follow_me(RelativePartyId::User));
+        let growl = StatusMove::new(
+            "Growl",
+            vec![StatusMoveEffect::Stage {
+                target: RelativePartyId::Opposing,
+                stat: Stat::Attack,
+                amount: -1,
+            }],
+        );
+        battlefield.attack(PartyId::Party1, &growl);
+        assert_eq!(battlefield.calc_stats(PartyId::Party2).attack, 66);
+        let spiked_hp = 105 - 105 / 8;
+        assert_eq!(battlefield.party(PartyId::Party1).active().hp(), spiked_hp);
+
+        battlefield.turn();
+        battlefield.attack(PartyId::Party2, &follow_me(RelativePartyId::Opposing));
+        battlefield.attack(PartyId::Party1, &growl);
+        assert_eq!(battlefield.calc_stats(PartyId::Party1).attack, 66);
+        assert_eq!(battlefield.calc_stats(PartyId::Party2).attack, 66);
+
+        let ember = SimpleDamagingMove::new("Ember".to_string(), 0)
+            .with_damage_kind(DamageKind::Fixed(10))
+            .with_secondary_effect(SecondaryEffect::new(
+                100,
+                RelativePartyId::Opposing,
+                SecondaryEffectKind::Status(StatusCondition::Burn),
+            ));
+        battlefield.attack(PartyId::Party1, &ember);
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert_eq!(mew.hp(), spiked_hp - 10);
+        assert!(mew.has_effect("burn"));
+        let opposing_mew = battlefield.party(PartyId::Party2).active();
+        assert_eq!(opposing_mew.hp(), 105);
+        assert!(!opposing_mew.has_status());
+
+        let protect = StatusMove::new("Protect", vec![StatusMoveEffect::Protect]);
+        battlefield.attack(PartyId::Party1, &protect);
+        battlefield.attack(PartyId::Party1, &ember);
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().hp(),
+            spiked_hp - 10
+        );
+        assert_eq!(battlefield.party(PartyId::Party2).active().hp(), 105);
+    }
+
+    #[test]
+    fn volatile_conditions_restrict_actions() {
+        let mew = || {
+            PartyItem::new(BattleDragon::new(Stats::new()))
+                .with_move(
+                    Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+                    35,
+                )
+                .with_move(Arc::new(StatusMove::new("Growl", vec![])), 40)
+        };
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew(), mew()]).unwrap(),
+            Party::new_from_vec(vec![mew()]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        let inflict = |name: &str, condition| {
+            StatusMove::new(name, vec![StatusMoveEffect::Volatile(condition)])
+        };
+
+        battlefield.attack(
+            PartyId::Party2,
+            &inflict("Taunt", VolatileCondition::Taunt { turns: 2 }),
+        );
+        assert_eq!(
+            battlefield.party(PartyId::Party1).active().usable_moves(),
+            vec![0]
+        );
+        assert!(battlefield
+            .submit_action(PartyId::Party1, Action::Move(1))
+            .is_err());
+
+        battlefield.attack(
+            PartyId::Party2,
+            &inflict("Block", VolatileCondition::Trap { turns: 2 }),
+        );
+        assert!(battlefield
+            .submit_action(PartyId::Party1, Action::Switch(1))
+            .is_err());
+
+        // Disabling fails until the dragon has used a move.
+        let disable = inflict("Disable", VolatileCondition::Disable { turns: 4 });
+        battlefield.attack(PartyId::Party2, &disable);
+        assert!(!battlefield
+            .party(PartyId::Party1)
+            .active()
+            .has_effect("disable"));
+        battlefield
+            .submit_action(PartyId::Party1, Action::Move(0))
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+        battlefield.attack(PartyId::Party2, &disable);
+        assert!(battlefield
+            .party(PartyId::Party1)
+            .active()
+            .usable_moves()
+            .is_empty());
+
+        // The conditions wear off, except for the disable.
+        assert!(battlefield
+            .submit_action(PartyId::Party1, Action::Move(0))
+            .is_err());
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+        let mew = battlefield.party(PartyId::Party1).active();
+        assert_eq!(mew.usable_moves(), vec![1]);
+        assert!(mew.may_switch());
+
+        battlefield
+            .submit_action(PartyId::Party1, Action::Switch(1))
+            .unwrap();
+        battlefield.run_turn();
+        assert!(!battlefield
+            .party(PartyId::Party1)
+            .member(0)
+            .unwrap()
+            .has_effect("disable"));
+    }
+
+    #[test]
+    fn legal_actions_explain_what_is_allowed() {
+        let mew = || {
+            PartyItem::new(BattleDragon::new(Stats::new()))
+                .with_move(
+                    Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+                    1,
+                )
+                .with_move(Arc::new(StatusMove::new("Growl", vec![])), 40)
+        };
+        let mut fainted = mew();
+        fainted.dragon.hp = 0;
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew(), fainted, mew()]).unwrap(),
+            Party::new_from_vec(vec![mew()]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        let legal = battlefield.legal_actions(PartyId::Party1);
+        assert_eq!(legal.switches, vec![2]);
+        assert!(legal.moves.iter().all(|m| m.is_usable()));
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Switch(1)),
+            Err(IllegalAction::Fainted(1).into())
+        );
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Switch(0)),
+            Err(IllegalAction::AlreadyActive(0).into())
+        );
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Move(4)),
+            Err(IllegalAction::NoSuchMove(4).into())
+        );
+
+        let taunt = StatusMove::new(
+            "Taunt",
+            vec![StatusMoveEffect::Volatile(VolatileCondition::Taunt {
+                turns: 3,
+            })],
+        );
+        battlefield.attack(PartyId::Party2, &taunt);
+        battlefield
+            .submit_action(PartyId::Party1, Action::Move(0))
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(0))
+            .unwrap();
+        battlefield.run_turn();
+
+        let legal = battlefield.legal_actions(PartyId::Party1);
+        assert_eq!(legal.moves[0].pp, 0);
+        assert_eq!(legal.moves[1].blocked_by.as_deref(), Some("taunt"));
+        assert!(legal.moves.iter().all(|m| !m.is_usable()));
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Move(0)),
+            Err(IllegalAction::NoPpLeft(0).into())
+        );
+        let error = battlefield
+            .submit_action(PartyId::Party1, Action::Move(1))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "the move in slot 1 is blocked by taunt");
+
+        let scarf = ItemData::new("Scarf", vec![ItemEffect::ChoiceLock]);
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew().with_item(scarf)]).unwrap(),
+            Party::new_from_vec(vec![mew()]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        battlefield
+            .submit_action(PartyId::Party1, Action::Move(1))
+            .unwrap();
+        battlefield
+            .submit_action(PartyId::Party2, Action::Move(1))
+            .unwrap();
+        battlefield.run_turn();
+        let legal = battlefield.legal_actions(PartyId::Party1);
+        assert!(legal.moves[1].is_usable());
+        assert_eq!(legal.moves[0].blocked_by.as_deref(), Some("Scarf"));
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Move(0)),
+            Err(IllegalAction::MoveBlocked {
+                slot: 0,
+                effect: "Scarf".to_string()
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn fainted_dragons_can_always_be_replaced() {
+        let mew = || {
+            PartyItem::new(BattleDragon::new(Stats::new())).with_move(
+                Arc::new(SimpleDamagingMove::new("Thrash".to_string(), 40)),
+                10,
+            )
+        };
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![mew(), mew()]).unwrap(),
+            Party::new_from_vec(vec![mew()]).unwrap(),
+            battle::TestMessenger,
+            3,
+        )
+        .unwrap();
+        let trapped = battlefield.party_mut(PartyId::Party1).active_mut();
+        trapped.add_effect(Box::new(effects::Trap::new(5)));
+        trapped.add_effect(Box::new(effects::Rampage::new("Thrash", 2)));
+        assert_eq!(
+            battlefield.submit_action(PartyId::Party1, Action::Switch(1)),
+            Err(IllegalAction::MoveForced(0).into())
+        );
+
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .damage(105);
+        let legal = battlefield.legal_actions(PartyId::Party1);
+        assert_eq!(legal.switches, vec![1]);
+        assert_eq!(legal.forced_move, None);
+        battlefield
+            .submit_action(PartyId::Party1, Action::Switch(1))
+            .unwrap();
+        battlefield.run_turn();
+        assert_eq!(battlefield.party(PartyId::Party1).active_index(), 1);
+    }
+
+    #[test]
+    fn bad_input_is_rejected_with_errors() {
+        assert_eq!(PartyId::from_u8(2), Err(EngineError::InvalidPartyId(2)));
+        assert!(matches!(
+            Party::new_from_vec(vec![]),
+            Err(EngineError::EmptyParty)
+        ));
+        let mew = || PartyItem::new(BattleDragon::new(Stats::new()));
+        let mut single = Party::new(mew());
+        assert_eq!(single.active_index(), 0);
+        single.add_dragon(BattleDragon::new(Stats::new()));
+        let mut battlefield =
+            Battlefield::new(single, Party::new(mew()), battle::TestMessenger).unwrap();
+        battlefield
+            .inventory_mut(PartyId::Party1)
+            .add(BagItemData::new("Potion", vec![]), 1);
+        assert_eq!(
+            battlefield.submit_action(
+                PartyId::Party1,
+                Action::UseItem {
+                    item: "Potion".to_string(),
+                    target: 2,
+                },
+            ),
+            Err(IllegalAction::NoSuchMember(2).into())
+        );
+
+        let mut party = Party::new_from_vec(vec![mew(), mew()]).unwrap();
+        assert_eq!(party.switch(5), Err(IllegalAction::NoSuchMember(5).into()));
+        party
+            .active_mut()
+            .add_effect(Box::new(effects::Trap::new(2)));
+        let error = party.switch(1).unwrap_err();
+        assert_eq!(error.to_string(), "the active dragon can not switch out");
+    }
+
+    #[test]
+    fn formats_report_every_violation() {
+        let mew = Arc::new(
+            DragonData::new("Mew", Stats::new())
+                .with_abilities(&["Synchronize"])
+                .with_learnset(&[(1, "Pound"), (40, "Psychic")]),
+        );
+        let member = |level, move_name: &str| {
+            PartyItem::new(BattleDragon::from_species(mew.clone()).with_level(level))
+                .with_move(
+                    Arc::new(SimpleDamagingMove::new(move_name.to_string(), 40)),
+                    10,
+                )
+                .with_ability(AbilityData::new("Levitate", vec![]))
+        };
+        let party = Party::new_from_vec(vec![
+            member(30, "Pound"),
+            member(30, "Psychic"),
+            PartyItem::new(
+                BattleDragon::new(Stats::new_exact(100, 100, 100, 200))
+                    .with_level(20)
+                    .with_values(Stats::new_exact(40, 0, 0, 0)),
+            ),
+        ])
+        .unwrap();
+
+        let mut format = Format::new("Little Cup");
+        assert!(format.validate(&party).is_empty());
+
+        format.max_party_size = Some(2);
+        format.max_level = Some(25);
+        format.species_clause = true;
+        format.check_learnsets = true;
+        format.max_stat_total = Some(450);
+        format.banned_abilities = vec!["Levitate".to_string()];
+        format.max_value = Some(MAX_VALUE);
+        let violations = format.validate(&party);
+        assert_eq!(
+            violations,
+            vec![
+                Violation::PartyTooLarge { size: 3, max: 2 },
+                Violation::LevelTooHigh {
+                    member: 0,
+                    level: 30,
+                    max: 25
+                },
+                Violation::BannedAbility {
+                    member: 0,
+                    ability: "Levitate".to_string()
+                },
+                Violation::IllegalAbility {
+                    member: 0,
+                    ability: "Levitate".to_string()
+                },
+                Violation::LevelTooHigh {
+                    member: 1,
+                    level: 30,
+                    max: 25
+                },
+                Violation::IllegalMove {
+                    member: 1,
+                    move_name: "Psychic".to_string()
+                },
+                Violation::BannedAbility {
+                    member: 1,
+                    ability: "Levitate".to_string()
+                },
+                Violation::IllegalAbility {
+                    member: 1,
+                    ability: "Levitate".to_string()
+                },
+                Violation::DuplicateSpecies {
+                    member: 1,
+                    species: "Mew".to_string()
+                },
+                Violation::UnknownSpecies { member: 2 },
+                Violation::StatTotalTooHigh {
+                    member: 2,
+                    total: 500,
+                    max: 450
+                },
+                Violation::ValueTooHigh {
+                    member: 2,
+                    stat: "attack",
+                    value: 40,
+                    max: 31
+                },
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "the party has 3 members, at most 2 are allowed"
+        );
+    }
+
+    #[test]
+    fn teams_survive_a_round_trip_through_text() {
+        let mut dex = Dex::new();
+        dex.add_species(
+            DragonData::new("Mew", Stats::new_exact(100, 100, 100, 100)).with_types(&["Psychic"]),
+        );
+        dex.add_species(DragonData::new("Ditto", Stats::new_exact(48, 48, 48, 48)));
+        dex.add_move(
+            Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40)),
+            35,
+        );
+        dex.add_item(ItemData::new("Leftovers", vec![]));
+        dex.add_ability(AbilityData::new("Synchronize", vec![]));
+
+        let text = "Sparky (Mew) (F) @ Leftovers\n\
+                    Ability: Synchronize\n\
+                    Level: 40\n\
+                    Values: 31 HP / 10 Spe\n\
+                    Cosmetics: shiny\n\
+                    - Pound\n\
+                    \n\
+                    Ditto\n\
+                    Level: 50\n";
+        let party = team::import(text, &dex).unwrap();
+        let sparky = party.active();
+        assert_eq!(sparky.dragon.nickname(), Some("Sparky"));
+        assert_eq!(sparky.dragon.level(), 40);
+        assert_eq!(sparky.dragon.max_hp(), 142);
+        assert_eq!(sparky.calc_stats().speed, 89);
+        assert!(sparky.dragon.has_type("Psychic"));
+        assert_eq!(sparky.item().unwrap().name, "Leftovers");
+        assert_eq!(sparky.moves()[0].pp, 35);
+        assert_eq!(team::export(&party).unwrap(), text);
+
+        let error = team::import("Mew\n- Pound\n- Psychic\n", &dex)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            EngineError::Parse {
+                line: 3,
+                message: "unknown move \"Psychic\"".to_string()
+            }
+        );
+        assert_eq!(error.to_string(), "line 3: unknown move \"Psychic\"");
+
+        let error = team::import("Mew\nValues: 4294967295 HP\n", &dex)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            EngineError::Parse {
+                line: 2,
+                message: "invalid values \"4294967295 HP\"".to_string()
+            }
+        );
+        let base = Stats::new_exact(u32::MAX, 1, 1, 1);
+        assert_eq!(
+            Stats::at_level(base, Stats::zero(), 100).attack,
+            u32::MAX / 100 + 5
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingMessenger {
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl battle::Messenger for RecordingMessenger {
+        fn on_attack(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            dragon: &DragonIdentity,
+            move_name: &str,
+        ) {
+            let line = format!("{} used {}!", dragon.name(), move_name);
+            self.lines.lock().unwrap().push(line);
+        }
+        fn on_damage_calculated(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _breakdown: &DamageBreakdown,
+        ) {
+        }
+        fn on_damage(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _amount: u32,
+        ) {
+        }
+        fn on_critical_hit(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+        ) {
+        }
+        fn on_miss(&self, _field: &Battlefield<Self>, _party: PartyId, _dragon: &DragonIdentity) {}
+        fn on_heal(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _amount: u32,
+        ) {
+        }
+        fn on_switch(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            dragon: &DragonIdentity,
+            _origin: u8,
+            _next: u8,
+        ) {
+            let line = format!("Go, {}!", dragon.name());
+            self.lines.lock().unwrap().push(line);
+        }
+        fn on_effect_applied(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _effect_desc: &str,
+        ) {
+        }
+        fn on_item_activated(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _item_name: &str,
+        ) {
+        }
+        fn on_bag_item(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _name: &str,
+            _target: u8,
+        ) {
+        }
+        fn on_message(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            _dragon: &DragonIdentity,
+            _message: &str,
+        ) {
+        }
+        fn on_progress(
+            &self,
+            _field: &Battlefield<Self>,
+            _party: PartyId,
+            dragon: &DragonIdentity,
+            _member: u8,
+            event: &ProgressEvent,
+        ) {
+            let line = match event {
+                ProgressEvent::Experience(amount) => {
+                    format!("{} gained {} experience!", dragon.name(), amount)
+                }
+                ProgressEvent::LevelUp(level) => {
+                    format!("{} grew to level {}!", dragon.name(), level)
+                }
+                ProgressEvent::WantsToLearn(move_name) => {
+                    format!("{} wants to learn {}!", dragon.name(), move_name)
+                }
+            };
+            self.lines.lock().unwrap().push(line);
+        }
+    }
+
+    #[test]
+    fn messengers_get_the_identity_of_dragons() {
+        let mew = Arc::new(DragonData::new("Mew", Stats::new()));
+        let pound = || Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40));
+        let party = Party::new_from_vec(vec![
+            PartyItem::new(BattleDragon::from_species(mew.clone())).with_move(pound(), 35),
+            PartyItem::new(
+                BattleDragon::from_species(mew.clone())
+                    .with_nickname("Sparky")
+                    .with_gender(Gender::Female)
+                    .with_cosmetic("shiny"),
+            ),
+        ])
+        .unwrap();
+        let mut battlefield = Battlefield::new_seeded(
+            party,
+            Party::new_from_vec(vec![PartyItem::new(BattleDragon::new(Stats::new()))]).unwrap(),
+            RecordingMessenger::default(),
+            1,
+        )
+        .unwrap();
+
+        battlefield.attack(PartyId::Party1, &*pound());
+        battlefield.switch(PartyId::Party1, 1).unwrap();
+        let sparky = battlefield.identity(PartyId::Party1);
+        assert_eq!(sparky.species.as_deref(), Some("Mew"));
+        assert!(sparky.has_cosmetic("shiny"));
+        assert_eq!(
+            *battlefield.messenger().lines.lock().unwrap(),
+            vec!["Mew used Pound!", "Go, Sparky!"]
+        );
+    }
+
+    #[test]
+    fn participants_gain_experience_and_level_up() {
+        let mew = Arc::new(
+            DragonData::new("Mew", Stats::new())
+                .with_growth(GrowthRate::MediumFast, 64)
+                .with_learnset(&[(1, "Pound"), (6, "Psychic")]),
+        );
+        let pound = || Arc::new(SimpleDamagingMove::new("Pound".to_string(), 40));
+        let member = |level, nickname| {
+            PartyItem::new(
+                BattleDragon::from_species(mew.clone())
+                    .with_level(level)
+                    .with_nickname(nickname),
+            )
+            .with_move(pound(), 35)
+        };
+        let mut weak_mew = member(5, "Weak");
+        weak_mew.dragon.hp = 1;
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![member(5, "Sparky"), member(5, "Fluffy")]).unwrap(),
+            Party::new_from_vec(vec![weak_mew]).unwrap(),
+            RecordingMessenger::default(),
+            1,
+        )
+        .unwrap();
+        battlefield.set_experience_gain(true);
+        assert_eq!(
+            battlefield
+                .party(PartyId::Party1)
+                .active()
+                .dragon
+                .experience(),
+            125
+        );
+
+        battlefield.switch(PartyId::Party1, 1).unwrap();
+        let max_hp = battlefield.party(PartyId::Party1).active().dragon.max_hp();
+        battlefield
+            .party_mut(PartyId::Party1)
+            .active_mut()
+            .dragon
+            .hp -= 3;
+        battlefield.messenger().lines.lock().unwrap().clear();
+        battlefield.attack(PartyId::Party1, &*pound());
+
+        // 64 * 5 / 7 = 45 experience is shared by both participants.
+        let lines = battlefield.messenger().lines.lock().unwrap().clone();
+        assert_eq!(
+            lines[1..],
+            [
+                "Sparky gained 22 experience!",
+                "Fluffy gained 22 experience!",
+            ]
+        );
+        let fluffy = battlefield.party(PartyId::Party1).active();
+        assert_eq!(fluffy.dragon.level(), 5);
+        assert_eq!(fluffy.dragon.experience(), 147);
+
+        let fluffy = battlefield.party_mut(PartyId::Party1).active_mut();
+        let events = fluffy.dragon.gain_experience(100);
+        assert_eq!(
+            events,
+            vec![
+                ProgressEvent::Experience(100),
+                ProgressEvent::LevelUp(6),
+                ProgressEvent::WantsToLearn("Psychic".to_string()),
+            ]
+        );
+        assert!(fluffy.dragon.max_hp() > max_hp);
+        assert_eq!(fluffy.dragon.hp, fluffy.dragon.max_hp() - 3);
+        let replaced = fluffy.learn_move(
+            Arc::new(SimpleDamagingMove::new("Psychic".to_string(), 90)),
+            10,
+            Some(0),
+        );
+        assert_eq!(replaced.unwrap().unwrap().attack().get_name(), "Pound");
+        assert_eq!(fluffy.moves()[0].attack().get_name(), "Psychic");
+        let known = fluffy.moves().len();
+        let replaced = fluffy.learn_move(
+            Arc::new(SimpleDamagingMove::new("Psychic".to_string(), 90)),
+            10,
+            Some(known),
+        );
+        assert_eq!(
+            replaced.err(),
+            Some(IllegalAction::NoSuchMove(known).into())
+        );
+        assert_eq!(fluffy.moves().len(), known);
+
+        let tabled =
+            DragonData::new("Mew", Stats::new()).with_growth(GrowthRate::Table(vec![10, 20]), 64);
+        let mut dragon = BattleDragon::from_species(Arc::new(tabled)).with_level(1);
+        dragon.gain_experience(u32::MAX);
+        dragon.gain_experience(1);
+        assert_eq!(dragon.level(), 3);
+        assert_eq!(dragon.experience(), u32::MAX);
+    }
+
+    #[test]
+    fn dragons_evolve_and_change_forms() {
+        let mut dex = Dex::new();
+        dex.add_species(
+            DragonData::new("Tyrogue", Stats::new_exact(35, 35, 35, 35))
+                .with_evolution(
+                    "Hitmonlee",
+                    EvolutionCondition::StatComparison {
+                        level: 20,
+                        first: Stat::Attack,
+                        second: Stat::Defense,
+                        order: StatOrder::Greater,
+                    },
+                )
+                .with_evolution(
+                    "Hitmontop",
+                    EvolutionCondition::StatComparison {
+                        level: 20,
+                        first: Stat::Attack,
+                        second: Stat::Defense,
+                        order: StatOrder::Equal,
+                    },
+                )
+                .with_evolution(
+                    "Hitmonchan",
+                    EvolutionCondition::Trade {
+                        held_item: Some("Boxing Glove".to_string()),
+                    },
+                ),
+        );
+        dex.add_species(DragonData::new(
+            "Hitmonlee",
+            Stats::new_exact(120, 53, 50, 87),
+        ));
+        dex.add_species(DragonData::new(
+            "Hitmontop",
+            Stats::new_exact(95, 95, 50, 70),
+        ));
+        dex.add_species(
+            DragonData::new("Hitmonchan", Stats::new_exact(105, 79, 50, 76))
+                .with_types(&["Fighting"]),
+        );
+        let tyrogue = || {
+            let species = dex.species("Tyrogue").unwrap().clone();
+            PartyItem::new(BattleDragon::from_species(species).with_level(20))
+        };
+
+        let mut item = tyrogue();
+        assert!(dex.evolve(&mut item, EvolutionTrigger::Trade).is_none());
+        item.dragon.hp = item.dragon.max_hp() / 2;
+        let species = dex.evolve(&mut item, EvolutionTrigger::LevelUp).unwrap();
+        assert_eq!(species.name, "Hitmontop");
+        assert_eq!(item.dragon.identity().species.as_deref(), Some("Hitmontop"));
+        assert_eq!(item.dragon.level(), 20);
+        assert_eq!(item.dragon.hp, item.dragon.max_hp() / 2);
+
+        let mut item = tyrogue().with_item(ItemData::new("Boxing Glove", vec![]));
+        let species = dex.evolve(&mut item, EvolutionTrigger::Trade).unwrap();
+        assert_eq!(species.name, "Hitmonchan");
+        assert!(item.item().is_none());
+        assert!(item.dragon.has_type("Fighting"));
+
+        let mega = Arc::new(
+            DragonData::new("Mega Hitmonchan", Stats::new_exact(145, 99, 200, 96))
+                .with_types(&["Fighting", "Steel"]),
+        );
+        let max_hp = item.dragon.max_hp();
+        let mut battlefield = Battlefield::new_seeded(
+            Party::new_from_vec(vec![item]).unwrap(),
+            Party::new_from_vec(vec![tyrogue()]).unwrap(),
+            battle::TestMessenger,
+            1,
+        )
+        .unwrap();
+        battlefield.change_form(PartyId::Party1, mega);
+        let hitmonchan = &battlefield.party(PartyId::Party1).active().dragon;
+        assert_eq!(hitmonchan.form().unwrap().name, "Mega Hitmonchan");
+        assert_eq!(hitmonchan.stats().attack, 63);
+        assert_eq!(hitmonchan.max_hp(), max_hp);
+        assert!(hitmonchan.has_type("Steel"));
+
+        let (party, _) = battlefield.into_parties();
+        let hitmonchan = &party.active().dragon;
+        assert!(hitmonchan.form().is_none());
+        assert_eq!(hitmonchan.stats().attack, 47);
+        assert!(!hitmonchan.has_type("Steel"));
+    }
+
+    #[test]
+    fn encounters_generate_wild_dragons() {
+        let mut dex = Dex::new();
+        dex.add_species(
+            DragonData::new("Rattata", Stats::new_exact(56, 35, 30, 72))
+                .with_abilities(&["Guts"])
+                .with_learnset(&[
+                    (1, "Tackle"),
+                    (4, "Tail Whip"),
+                    (7, "Quick Attack"),
+                    (10, "Bite"),
+                    (13, "Hyper Fang"),
+                ]),
+        );
+        dex.add_species(DragonData::new(
+            "Hoothoot",
+            Stats::new_exact(30, 30, 60, 50),
+        ));
+        for name in ["Tackle", "Tail Whip", "Quick Attack", "Bite", "Hyper Fang"] {
+            dex.add_move(Arc::new(SimpleDamagingMove::new(name.to_string(), 40)), 30);
+        }
+        dex.add_ability(AbilityData::new("Guts", vec![]));
+
+        let route = Encounter::new("Route 1")
+            .with_species("Rattata", 1, 10, 15)
+            .with_modifier("night", 0)
+            .with_species("Hoothoot", 0, 5, 5)
+            .with_modifier("night", 1);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let rattata = route.generate(&dex, &[], &mut rng).unwrap().unwrap();
+            let level = rattata.dragon.level();
+            assert!((10..=15).contains(&level));
+            assert_eq!(rattata.dragon.species().unwrap().name, "Rattata");
+            assert_eq!(rattata.ability().unwrap().name, "Guts");
+            let moves: Vec<&str> = rattata
+                .moves()
+                .iter()
+                .map(|m| m.attack().get_name())
+                .collect();
+            if level >= 13 {
+                assert_eq!(moves, ["Tail Whip", "Quick Attack", "Bite", "Hyper Fang"]);
+            } else {
+                assert_eq!(moves, ["Tackle", "Tail Whip", "Quick Attack", "Bite"]);
+            }
+            assert!(rattata.dragon.values().hp <= 31);
+        }
+        let hoothoot = route.generate(&dex, &["night"], &mut rng).unwrap().unwrap();
+        assert_eq!(hoothoot.dragon.level(), 5);
+        assert!(hoothoot.moves().is_empty());
+
+        let generate = |seed| {
+            let dragon = route
+                .generate(&dex, &[], &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+                .unwrap()
+                .dragon;
+            (dragon.level(), *dragon.values())
+        };
+        assert_eq!(generate(3), generate(3));
+
+        assert!(Encounter::new("Cave")
+            .generate(&dex, &[], &mut rng)
+            .unwrap()
+            .is_none());
+        let cave = Encounter::new("Cave").with_species("Zubat", 1, 3, 3);
+        assert_eq!(
+            cave.generate(&dex, &[], &mut rng).err().unwrap(),
+            EngineError::NotInDex {
+                kind: "species",
+                name: "Zubat".to_string()
+            }
+        );
+
+        let crowded = Encounter::new("Crowded")
+            .with_species("Zubat", u32::MAX, 3, 3)
+            .with_species("Geodude", u32::MAX, 5, 5);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..32 {
+            let (slot, _) = crowded.choose(&[], &mut rng).unwrap();
+            seen.insert(slot.species.clone());
+        }
+        assert_eq!(seen.len(), 2);
+    }
 }
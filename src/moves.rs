@@ -1,18 +1,31 @@
-use std::cmp::min;
+use std::{num::NonZeroU32, sync::Arc};
 
-use rand::Rng;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    battle::Messenger,
+    damage::{self, CritRules, DamageCalculator, DamageInput},
+    dragon::{Stat, StatStages},
+    effect::{
+        effects::{
+            Charging, Flinch, Protect, Rampage, Recharge, Redirect, StatStageModifier, Substitute,
+        },
+        EffectAction, LongTermEffectTrait, StatusCondition, VolatileCondition,
+    },
     events::MoveEvent,
     party::{PartyItem, RelativePartyId},
+    side::{SideEffectData, SideEffectTrait, SideEffects, WeatherData},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct MoveStats {
     pub accuracy: u32,
     pub base_power: u32,
-    pub(crate) crit_calc: u8,
+    /// The crit stage of the move. Effects may raise it in their
+    /// `offending` hooks.
+    pub crit_calc: u8,
+    /// The name of the type of the move, if it has one.
+    pub move_type: Option<String>,
 }
 
 impl MoveStats {
@@ -21,112 +34,931 @@ impl MoveStats {
             accuracy,
             base_power,
             crit_calc: 0,
+            move_type: None,
         }
     }
+
+    pub fn with_type(mut self, move_type: &str) -> Self {
+        self.move_type = Some(move_type.to_string());
+        self
+    }
+}
+
+/// A position where a dragon charging a move is out of reach
+/// of most moves.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SemiInvulnerable {
+    Air,
+    Underground,
+    Underwater,
+}
+
+/// Properties of a move that effects may react to.
+#[derive(Clone, Copy, Default)]
+pub struct AttackFlags {
+    /// The move makes physical contact with the defender.
+    pub contact: bool,
+    /// The move can hit dragons in this semi-invulnerable position.
+    pub reaches: Option<SemiInvulnerable>,
+    /// The move does not deal damage.
+    pub status: bool,
 }
 
+/// What happens to a move aimed at a dragon before it reaches the
+/// `defending` hooks of the dragon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interception {
+    /// The move reaches the dragon.
+    Hit,
+    /// The move is stopped.
+    Blocked,
+    /// The move is pulled to a slot of the field, relative to the
+    /// dragon intercepting it. The field decides who is in the slot and
+    /// hits that dragon instead, which may be the user of the move.
+    Redirected(RelativePartyId),
+}
+
+/// The data an attack is calculated with. It is created with the stat
+/// stages of both dragons, then passed through the `offending` hooks of
+/// the attacker's effects, and finally the `defending` hooks of the
+/// defender's effects.
+#[derive(Clone)]
+pub struct AttackContext {
+    pub attacker_stages: StatStages,
+    pub defender_stages: StatStages,
+    pub move_stats: MoveStats,
+    pub flags: AttackFlags,
+    /// The final damage of the attack is multiplied by this.
+    /// Effects may change it to make the attack stronger or weaker.
+    pub modifier: f32,
+}
+
+impl AttackContext {
+    pub fn new(
+        attacker_stages: StatStages,
+        defender_stages: StatStages,
+        move_stats: MoveStats,
+        flags: AttackFlags,
+    ) -> Self {
+        Self {
+            attacker_stages,
+            defender_stages,
+            move_stats,
+            flags,
+            modifier: 1.,
+        }
+    }
+}
+
+/// The outcome of a move.
 pub enum MoveResult {
-    Succeeded(Option<MoveEvent>),
+    /// The move reached the opponent, so the `on_hit` hooks of the
+    /// effects of the opponent react to it.
+    Hit(Vec<MoveEvent>),
+    /// The move worked without reaching the opponent, for example
+    /// because it was only used on the user or is being charged.
+    Succeeded(Vec<MoveEvent>),
     Failed,
     Missed,
 }
 
+/// Calculates the classic damage formula for a level 50 dragon, without
+/// any multipliers.
 pub fn calculate_static_damage(user_attack: u32, opponent_defense: u32, base_power: u32) -> u32 {
-    22 * user_attack * base_power / opponent_defense / 50 + 2
+    damage::base_damage(50, user_attack, opponent_defense, base_power)
 }
 
-pub fn calculate_random_damage(
-    user_attack: u32,
-    opponent_defense: u32,
-    base_power: u32,
-    crit: u8,
-) -> u32 {
-    let base_damage = calculate_static_damage(user_attack, opponent_defense, base_power);
-    let crit_chance = (&[24., 8., 2., 1.])[min(3, crit) as usize];
-    if rand::thread_rng().gen_bool(1. / crit_chance) {
-        base_damage + base_damage / 2
-    } else {
-        base_damage
+/// Calculates `value * numerator / denominator` without overflowing,
+/// saturating if the result does not fit.
+fn scale(value: u32, numerator: u32, denominator: NonZeroU32) -> u32 {
+    let scaled = value as u64 * numerator as u64 / denominator.get() as u64;
+    scaled.min(u32::MAX as u64) as u32
+}
+
+/// Calculates `percent` percent of `value` without overflowing.
+fn percent_of(value: u32, percent: u32) -> u32 {
+    (value as u64 * percent as u64 / 100).min(u32::MAX as u64) as u32
+}
+
+/// The part of the battlefield a move can see and change while it is
+/// being used.
+pub struct MoveContext<'a> {
+    pub user: &'a mut PartyItem,
+    pub opponent: &'a mut PartyItem,
+    pub user_side: &'a SideEffects,
+    pub opponent_side: &'a SideEffects,
+    pub rng: &'a mut dyn RngCore,
+    pub crit_rules: &'a CritRules,
+    pub damage_calculator: &'a dyn DamageCalculator,
+    pub(crate) actions: Vec<EffectAction>,
+}
+
+impl MoveContext<'_> {
+    /// Aims an attack of the user at the opponent. The effects of the
+    /// dragon the attack is aimed at may stop it, or pull it to another
+    /// slot whose dragon is asked in turn. A move is pulled at most once.
+    /// Interceptions are reported with a message. Returns the slot the
+    /// attack reaches, relative to the user, or None if it is stopped.
+    pub fn aim(&mut self, attack: &AttackContext) -> Option<RelativePartyId> {
+        let mut slot = RelativePartyId::Opposing;
+        let mut redirected = false;
+        while let Some((name, interception)) = self.dragon(slot).intercept(attack) {
+            let name = name.to_string();
+            match interception {
+                Interception::Redirected(to) if !redirected => {
+                    // `to` is relative to the dragon in `slot`.
+                    let to = match slot {
+                        RelativePartyId::User => to,
+                        RelativePartyId::Opposing => to.opposing(),
+                    };
+                    if to == slot {
+                        break;
+                    }
+                    self.emit(MoveEvent::Message {
+                        rel_party_id: slot,
+                        message: format!("drew the move away with {}", name),
+                    });
+                    slot = to;
+                    redirected = true;
+                }
+                Interception::Hit | Interception::Redirected(_) => break,
+                Interception::Blocked => {
+                    self.emit(MoveEvent::Message {
+                        rel_party_id: slot,
+                        message: format!("was protected by {}", name),
+                    });
+                    return None;
+                }
+            }
+        }
+        Some(slot)
+    }
+
+    /// The dragon in a slot of the field, relative to the user.
+    pub fn dragon(&self, slot: RelativePartyId) -> &PartyItem {
+        match slot {
+            RelativePartyId::User => self.user,
+            RelativePartyId::Opposing => self.opponent,
+        }
+    }
+
+    fn dragon_mut(&mut self, slot: RelativePartyId) -> &mut PartyItem {
+        match slot {
+            RelativePartyId::User => self.user,
+            RelativePartyId::Opposing => self.opponent,
+        }
+    }
+
+    /// Sends an event to the messenger once the move is finished,
+    /// whether it succeeded or not.
+    pub fn emit(&mut self, event: MoveEvent) {
+        self.actions.push(EffectAction::Event(event));
+    }
+
+    /// Decides whether an attack hits, from the accuracy of the move
+    /// and the accuracy and evasion stages in the attack. Moves that
+    /// can not miss do not consume randomness.
+    pub fn accuracy_check(&mut self, attack: &AttackContext) -> bool {
+        let stage = (attack.attacker_stages.accuracy - attack.defender_stages.evasion).clamp(-6, 6);
+        let multiplier = if stage >= 0 {
+            (3 + stage) as f64 / 3.
+        } else {
+            3. / (3 - stage) as f64
+        };
+        let chance = attack.move_stats.accuracy as f64 / 100. * multiplier;
+        chance >= 1. || self.rng.gen_bool(chance.max(0.))
+    }
+
+    /// Attaches an effect to the user or the opponent once the move
+    /// has succeeded, the same way effects attach other effects.
+    pub fn add_effect(&mut self, target: RelativePartyId, effect: Box<dyn LongTermEffectTrait>) {
+        self.actions.push(EffectAction::AddEffect(target, effect));
+    }
+
+    /// Adds a side effect to the side of the target once the move has
+    /// succeeded.
+    pub fn add_side_effect(&mut self, target: RelativePartyId, effect: Box<dyn SideEffectTrait>) {
+        self.actions
+            .push(EffectAction::AddSideEffect(target, effect));
+    }
+
+    /// Sets the weather of the battlefield once the move has succeeded.
+    pub fn set_weather(&mut self, weather: WeatherData) {
+        self.actions.push(EffectAction::SetWeather(weather));
+    }
+
+    /// Rolls the chance of each secondary effect, and adds the ones
+    /// that trigger. Effects targeting a fainted dragon are skipped.
+    /// Returns an event for each triggered effect.
+    pub fn apply_secondary_effects(&mut self, effects: &[SecondaryEffect]) -> Vec<MoveEvent> {
+        let mut events = vec![];
+        for secondary in effects {
+            let target_hp = match secondary.target {
+                RelativePartyId::User => self.user.hp(),
+                RelativePartyId::Opposing => self.opponent.hp(),
+            };
+            if target_hp == 0 || self.rng.gen_range(0..100) >= secondary.chance {
+                continue;
+            }
+            let effect: Box<dyn LongTermEffectTrait> = match secondary.kind {
+                SecondaryEffectKind::Status(status) => status.effect(self.rng),
+                SecondaryEffectKind::Volatile(condition) => {
+                    let target = match secondary.target {
+                        RelativePartyId::User => &*self.user,
+                        RelativePartyId::Opposing => &*self.opponent,
+                    };
+                    match condition.effect(target, self.rng) {
+                        Some(effect) => effect,
+                        None => continue,
+                    }
+                }
+                SecondaryEffectKind::Stage { stat, amount } => {
+                    Box::new(StatStageModifier::new(stat, amount))
+                }
+                SecondaryEffectKind::Flinch => Box::new(Flinch),
+            };
+            events.push(MoveEvent::Effected {
+                rel_party_id: secondary.target,
+                description: effect.get_name().to_string(),
+            });
+            self.add_effect(secondary.target, effect);
+        }
+        events
     }
 }
 
-pub trait MoveTrait<T: Messenger> {
-    fn attack_opponent(
-        &self,
-        opponent: &mut PartyItem,
-        user: &PartyItem,
-        messenger: &T,
-    ) -> MoveResult;
-    fn apply_to_user(
-        &self,
-        _user: &mut PartyItem,
-        _opponent: &PartyItem,
-        _messenger: &T,
-    ) -> Option<MoveEvent> {
+/// An effect a move has a chance to cause after it hits.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SecondaryEffect {
+    /// The chance of the effect in percent.
+    pub chance: u8,
+    /// Who the effect is applied to, relative to the user of the move.
+    pub target: RelativePartyId,
+    pub kind: SecondaryEffectKind,
+}
+
+impl SecondaryEffect {
+    pub fn new(chance: u8, target: RelativePartyId, kind: SecondaryEffectKind) -> Self {
+        Self {
+            chance,
+            target,
+            kind,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum SecondaryEffectKind {
+    Status(StatusCondition),
+    Volatile(VolatileCondition),
+    /// Changes a stat stage for as long as the dragon battles.
+    Stage {
+        stat: Stat,
+        amount: i8,
+    },
+    Flinch,
+}
+
+/// A move known by a dragon, with its remaining power points.
+#[derive(Clone)]
+pub struct MoveSlot {
+    pub(crate) attack: Arc<dyn MoveTrait>,
+    pub pp: u8,
+    pub max_pp: u8,
+}
+
+impl MoveSlot {
+    pub fn new(attack: Arc<dyn MoveTrait>, max_pp: u8) -> Self {
+        Self {
+            attack,
+            pp: max_pp,
+            max_pp,
+        }
+    }
+
+    pub fn attack(&self) -> &dyn MoveTrait {
+        &*self.attack
+    }
+}
+
+pub trait MoveTrait: Send + Sync {
+    fn attack_opponent(&self, ctx: &mut MoveContext) -> MoveResult;
+    fn apply_to_user(&self, _ctx: &mut MoveContext) -> Option<MoveEvent> {
         None
     }
+    /// The stats and flags the move attacks with, if it deals damage
+    /// through the damage formula. Used to preview its damage.
+    fn move_stats(&self) -> Option<(MoveStats, AttackFlags)> {
+        None
+    }
+    /// Returns true if the move does not deal damage.
+    fn is_status(&self) -> bool {
+        false
+    }
     fn get_name(&self) -> &str;
 }
 
+/// How the damage of a [`SimpleDamagingMove`] is decided.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DamageKind {
+    /// The damage formula of the battlefield.
+    #[default]
+    Formula,
+    /// Always the same amount of damage.
+    Fixed(u32),
+    /// Damage equal to the level of the user.
+    Level,
+    /// The given percentage of the current HP of the target, at least 1.
+    CurrentHpPercent(u32),
+    /// Knocks out the target in one hit. Fails against
+    /// targets with a higher level than the user.
+    OneHitKo,
+}
+
+/// How many times a move hits. The number of hits is chosen at random
+/// from the range, and the move stops early if the target faints.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hits {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl Default for Hits {
+    fn default() -> Self {
+        Self { min: 1, max: 1 }
+    }
+}
+
+/// How a [`SimpleDamagingMove`] spans over turns.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MoveTiming {
+    /// The move is used in a single turn.
+    #[default]
+    Normal,
+    /// The move is charged in the first turn and hits in the next one.
+    /// While charging, the user may be in a semi-invulnerable position.
+    Charge {
+        message: String,
+        #[serde(default)]
+        position: Option<SemiInvulnerable>,
+    },
+    /// The user has to recharge in the turn after the move hit.
+    Recharge,
+    /// The user is locked into the move for `min` to `max` turns,
+    /// then becomes confused.
+    Rampage { min: u8, max: u8 },
+}
+
 #[derive(serde::Deserialize)]
 pub struct SimpleDamagingMove {
     base_power: u32,
     crit_boost: u8,
+    #[serde(default)]
+    contact: bool,
+    #[serde(default)]
+    move_type: Option<String>,
+    #[serde(default)]
+    secondary_effects: Vec<SecondaryEffect>,
+    #[serde(default)]
+    damage_kind: DamageKind,
+    #[serde(default)]
+    hits: Hits,
+    /// The user takes `numerator / denominator` of the damage dealt.
+    #[serde(default)]
+    recoil: Option<(u32, NonZeroU32)>,
+    /// The user heals `numerator / denominator` of the damage dealt.
+    #[serde(default)]
+    drain: Option<(u32, NonZeroU32)>,
+    #[serde(default)]
+    reaches: Option<SemiInvulnerable>,
+    #[serde(default)]
+    timing: MoveTiming,
+    /// The accuracy of the move in percent, 100 if not set.
+    #[serde(default)]
+    accuracy: Option<u32>,
     name: String,
 }
 
 impl SimpleDamagingMove {
     pub fn new(name: String, base_power: u32) -> Self {
-        Self {
-            base_power,
-            crit_boost: 0,
-            name,
-        }
+        Self::new_crit(name, base_power, 0)
     }
 
     pub fn new_crit(name: String, base_power: u32, crit_boost: u8) -> Self {
         Self {
             base_power,
             crit_boost,
+            contact: false,
+            move_type: None,
+            secondary_effects: vec![],
+            damage_kind: DamageKind::Formula,
+            hits: Hits::default(),
+            recoil: None,
+            drain: None,
+            reaches: None,
+            timing: MoveTiming::Normal,
+            accuracy: None,
             name,
         }
     }
-}
 
-impl<T: Messenger> MoveTrait<T> for SimpleDamagingMove {
-    fn attack_opponent(
-        &self,
-        opponent: &mut PartyItem,
-        user: &PartyItem,
-        _messenger: &T,
-    ) -> MoveResult {
+    pub fn with_type(mut self, move_type: &str) -> Self {
+        self.move_type = Some(move_type.to_string());
+        self
+    }
+
+    /// Adds an effect the move has a chance to cause after it hits.
+    pub fn with_secondary_effect(mut self, effect: SecondaryEffect) -> Self {
+        self.secondary_effects.push(effect);
+        self
+    }
+
+    /// Sets whether the move makes contact with the defender.
+    pub fn with_contact(mut self, contact: bool) -> Self {
+        self.contact = contact;
+        self
+    }
+
+    pub fn with_damage_kind(mut self, damage_kind: DamageKind) -> Self {
+        self.damage_kind = damage_kind;
+        self
+    }
+
+    /// Makes the move hit between `min` and `max` times.
+    pub fn with_hits(mut self, min: u8, max: u8) -> Self {
+        self.hits = Hits { min, max };
+        self
+    }
+
+    /// Makes the user take `numerator / denominator` of
+    /// the damage dealt by the move.
+    pub fn with_recoil(mut self, numerator: u32, denominator: NonZeroU32) -> Self {
+        self.recoil = Some((numerator, denominator));
+        self
+    }
+
+    /// Makes the user heal `numerator / denominator` of
+    /// the damage dealt by the move.
+    pub fn with_drain(mut self, numerator: u32, denominator: NonZeroU32) -> Self {
+        self.drain = Some((numerator, denominator));
+        self
+    }
+
+    /// Lets the move hit dragons in a semi-invulnerable position.
+    pub fn with_reach(mut self, position: SemiInvulnerable) -> Self {
+        self.reaches = Some(position);
+        self
+    }
+
+    pub fn with_timing(mut self, timing: MoveTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn with_accuracy(mut self, accuracy: u32) -> Self {
+        self.accuracy = Some(accuracy);
+        self
+    }
+
+    fn attack_stats(&self) -> (MoveStats, AttackFlags) {
         let move_stats = MoveStats {
-            accuracy: 100,
+            accuracy: self.accuracy.unwrap_or(100),
             base_power: self.base_power,
             crit_calc: self.crit_boost,
+            move_type: self.move_type.clone(),
+        };
+        let flags = AttackFlags {
+            contact: self.contact,
+            reaches: self.reaches,
+            status: false,
+        };
+        (move_stats, flags)
+    }
+
+    /// Deals the damage of one hit to the dragon in `target`. Returns
+    /// the damage dealt, whether the hit reached the dragon instead of
+    /// its substitute, and the events describing the hit.
+    fn hit(
+        &self,
+        ctx: &mut MoveContext,
+        target: RelativePartyId,
+        attack: &AttackContext,
+    ) -> (u32, bool, Vec<MoveEvent>) {
+        let user = &*ctx.user;
+        let (defender, defender_side) = match target {
+            RelativePartyId::User => (&*ctx.user, ctx.user_side),
+            RelativePartyId::Opposing => (&*ctx.opponent, ctx.opponent_side),
+        };
+        let (damage, critical, breakdown) = match self.damage_kind {
+            DamageKind::Formula => {
+                let critical = ctx.crit_rules.roll(attack.move_stats.crit_calc, ctx.rng);
+                let roll = ctx.rng.gen_range(ctx.damage_calculator.roll_range());
+                let breakdown = ctx.damage_calculator.calculate(&DamageInput {
+                    attacker: user,
+                    defender,
+                    defender_side,
+                    attack,
+                    crit_rules: ctx.crit_rules,
+                    critical,
+                    roll,
+                });
+                (breakdown.damage, critical, Some(breakdown))
+            }
+            DamageKind::Fixed(amount) => (amount, false, None),
+            DamageKind::Level => (user.dragon.level() as u32, false, None),
+            DamageKind::CurrentHpPercent(percent) => {
+                (percent_of(defender.hp(), percent).max(1), false, None)
+            }
+            DamageKind::OneHitKo => (defender.hp(), false, None),
         };
-        let stats = user.offend(move_stats, opponent.calc_stages(), |m, o| {
-            opponent.defend(m, o)
+        let mut events = vec![];
+        if let Some(breakdown) = breakdown {
+            events.push(MoveEvent::Calculated {
+                rel_party_id: target,
+                breakdown,
+            });
+        }
+        let defender = ctx.dragon_mut(target);
+        let after_absorb = defender.absorb(damage);
+        let left = after_absorb.min(defender.hp());
+        let absorbed = after_absorb < damage;
+        if absorbed {
+            events.push(MoveEvent::Message {
+                rel_party_id: target,
+                message: "took the hit with its substitute".to_string(),
+            });
+        }
+        defender.damage(left);
+        events.push(MoveEvent::Damaged {
+            rel_party_id: target,
+            damage_amount: left,
+            critical,
         });
-        let (user_stages, move_stats, opponent_stages) = match stats {
+        let dealt = if absorbed { damage } else { left };
+        (dealt, !absorbed, events)
+    }
+}
+
+impl MoveTrait for SimpleDamagingMove {
+    fn attack_opponent(&self, ctx: &mut MoveContext) -> MoveResult {
+        match &self.timing {
+            MoveTiming::Charge { message, position } if !ctx.user.has_effect("charging") => {
+                ctx.add_effect(
+                    RelativePartyId::User,
+                    Box::new(Charging::new(&self.name, *position)),
+                );
+                return MoveResult::Succeeded(vec![MoveEvent::Message {
+                    rel_party_id: RelativePartyId::User,
+                    message: message.clone(),
+                }]);
+            }
+            MoveTiming::Charge { .. } => {
+                ctx.user.remove_effect("charging");
+            }
+            MoveTiming::Rampage { min, max } if !ctx.user.has_effect("rampage") => {
+                let uses = ctx.rng.gen_range(*min..=(*max).max(*min));
+                ctx.add_effect(
+                    RelativePartyId::User,
+                    Box::new(Rampage::new(&self.name, uses)),
+                );
+            }
+            _ => {}
+        }
+
+        let (move_stats, flags) = self.attack_stats();
+        let attack = match ctx
+            .user
+            .prepare_attack(move_stats.clone(), flags, ctx.opponent)
+        {
             None => return MoveResult::Failed,
-            Some(s) => s,
+            Some(attack) => attack,
         };
-        let user_stats = user.dragon.stats().apply_stages(user_stages);
-        let opponent_stats = opponent.dragon.stats().apply_stages(opponent_stages);
-
-        let final_damage = calculate_random_damage(
-            user_stats.attack,
-            opponent_stats.defense,
-            move_stats.base_power,
-            move_stats.crit_calc,
-        );
-        opponent.damage(final_damage);
-
-        MoveResult::Succeeded(Some(MoveEvent::Damaged {
-            rel_party_id: RelativePartyId::Opposing,
-            damage_amount: final_damage,
-        }))
+        let target = match ctx.aim(&attack) {
+            None => return MoveResult::Failed,
+            Some(target) => target,
+        };
+        // A redirected attack is prepared again against its new target.
+        let attack = match target {
+            RelativePartyId::Opposing => Some(attack),
+            RelativePartyId::User => ctx.user.prepare_attack(move_stats, flags, ctx.user),
+        };
+        let attack = match attack.and_then(|a| ctx.dragon(target).defend(a)) {
+            None => return MoveResult::Failed,
+            Some(attack) => attack,
+        };
+        if self.damage_kind == DamageKind::OneHitKo
+            && ctx.dragon(target).dragon.level() > ctx.user.dragon.level()
+        {
+            return MoveResult::Failed;
+        }
+        if !ctx.accuracy_check(&attack) {
+            return MoveResult::Missed;
+        }
+
+        let hits = if self.hits.max > self.hits.min {
+            ctx.rng.gen_range(self.hits.min..=self.hits.max)
+        } else {
+            self.hits.min
+        };
+        let mut events = vec![];
+        let mut total = 0u32;
+        let mut count = 0;
+        let mut reached = false;
+        while count < hits && ctx.dragon(target).hp() > 0 {
+            let (damage, hit_reached, hit_events) = self.hit(ctx, target, &attack);
+            events.extend(hit_events);
+            total = total.saturating_add(damage);
+            count += 1;
+            reached |= hit_reached;
+        }
+        if hits > 1 {
+            events.push(MoveEvent::Message {
+                rel_party_id: target,
+                message: format!("was hit {} times", count),
+            });
+        }
+        if self.damage_kind == DamageKind::OneHitKo {
+            events.push(MoveEvent::Message {
+                rel_party_id: target,
+                message: "was knocked out in one hit".to_string(),
+            });
+        }
+
+        // Drain and recoil need some damage to have been dealt, but
+        // then amount to at least 1 HP.
+        if let Some((numerator, denominator)) = self.drain.filter(|_| total > 0) {
+            let amount = scale(total, numerator, denominator).max(1);
+            events.push(MoveEvent::Healed {
+                rel_party_id: RelativePartyId::User,
+                heal_amount: ctx.user.heal(amount),
+            });
+        }
+        if let Some((numerator, denominator)) = self.recoil.filter(|_| total > 0) {
+            let amount = scale(total, numerator, denominator)
+                .max(1)
+                .min(ctx.user.hp());
+            ctx.user.damage(amount);
+            events.push(MoveEvent::Damaged {
+                rel_party_id: RelativePartyId::User,
+                damage_amount: amount,
+                critical: false,
+            });
+        }
+        if self.timing == MoveTiming::Recharge {
+            ctx.add_effect(RelativePartyId::User, Box::new(Recharge::new(&self.name)));
+        }
+        // Effects aimed at the opponent go to the dragon which was hit,
+        // and are lost if a substitute took every hit.
+        let secondary_effects: Vec<_> = self
+            .secondary_effects
+            .iter()
+            .filter(|e| reached || e.target == RelativePartyId::User)
+            .map(|e| match e.target {
+                RelativePartyId::User => e.clone(),
+                RelativePartyId::Opposing => SecondaryEffect {
+                    target,
+                    ..e.clone()
+                },
+            })
+            .collect();
+        events.extend(ctx.apply_secondary_effects(&secondary_effects));
+        if reached && target == RelativePartyId::Opposing {
+            MoveResult::Hit(events)
+        } else {
+            MoveResult::Succeeded(events)
+        }
+    }
+
+    /// Only moves using the damage formula can be previewed.
+    fn move_stats(&self) -> Option<(MoveStats, AttackFlags)> {
+        match self.damage_kind {
+            DamageKind::Formula => Some(self.attack_stats()),
+            _ => None,
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// What a status move does when it succeeds.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum StatusMoveEffect {
+    /// Changes a stat stage of the target for as long as it battles.
+    Stage {
+        target: RelativePartyId,
+        stat: Stat,
+        amount: i8,
+    },
+    /// Inflicts a status condition on the opponent.
+    Status(StatusCondition),
+    /// Inflicts a volatile condition on the opponent.
+    Volatile(VolatileCondition),
+    /// Heals `percent`% of the maximum HP of the user.
+    Heal { percent: u32 },
+    /// Adds a side effect to the side of the target.
+    SideEffect {
+        target: RelativePartyId,
+        effect: SideEffectData,
+    },
+    /// Sets the weather of the battlefield.
+    Weather(WeatherData),
+    /// Stops the moves used against the user for the rest of the turn.
+    /// The chance of success is divided by 3 for each time the move
+    /// succeeded in a row before.
+    Protect,
+    /// Puts up a substitute with 1/4 of the maximum HP of the user,
+    /// paid from the HP of the user.
+    Substitute,
+    /// Pulls the moves aimed at the user to a slot of the field for
+    /// the rest of the turn. The slot is relative to the user.
+    Redirect { slot: RelativePartyId },
+}
+
+/// A move which does not deal damage, only applies its effects. If any
+/// of the effects is used on the opponent, the move may be intercepted
+/// by the opponent, and has to pass the `defending` hooks of the
+/// opponent and an accuracy check.
+#[derive(Serialize, Deserialize)]
+pub struct StatusMove {
+    name: String,
+    /// The accuracy of the move in percent. Moves without
+    /// one can not miss.
+    #[serde(default)]
+    accuracy: Option<u32>,
+    #[serde(default)]
+    move_type: Option<String>,
+    effects: Vec<StatusMoveEffect>,
+}
+
+impl StatusMove {
+    pub fn new(name: &str, effects: Vec<StatusMoveEffect>) -> Self {
+        Self {
+            name: name.to_string(),
+            accuracy: None,
+            move_type: None,
+            effects,
+        }
+    }
+
+    pub fn with_accuracy(mut self, accuracy: u32) -> Self {
+        self.accuracy = Some(accuracy);
+        self
+    }
+
+    pub fn with_type(mut self, move_type: &str) -> Self {
+        self.move_type = Some(move_type.to_string());
+        self
+    }
+
+    /// Returns true if the move has an effect used on the opponent.
+    pub fn targets_opponent(&self) -> bool {
+        self.effects.iter().any(|e| {
+            matches!(
+                e,
+                StatusMoveEffect::Status(_)
+                    | StatusMoveEffect::Volatile(_)
+                    | StatusMoveEffect::Stage {
+                        target: RelativePartyId::Opposing,
+                        ..
+                    }
+            )
+        })
+    }
+}
+
+impl MoveTrait for StatusMove {
+    fn attack_opponent(&self, ctx: &mut MoveContext) -> MoveResult {
+        // The slot the effects used on the opponent go to.
+        let mut slot = RelativePartyId::Opposing;
+        if self.targets_opponent() {
+            let mut move_stats = MoveStats::new(0, self.accuracy.unwrap_or(100));
+            move_stats.move_type = self.move_type.clone();
+            let flags = AttackFlags {
+                status: true,
+                ..AttackFlags::default()
+            };
+            let attack = match ctx
+                .user
+                .prepare_attack(move_stats.clone(), flags, ctx.opponent)
+            {
+                None => return MoveResult::Failed,
+                Some(attack) => attack,
+            };
+            slot = match ctx.aim(&attack) {
+                None => return MoveResult::Failed,
+                Some(slot) => slot,
+            };
+            let attack = match slot {
+                RelativePartyId::Opposing => Some(attack),
+                RelativePartyId::User => ctx.user.prepare_attack(move_stats, flags, ctx.user),
+            };
+            let attack = match attack.and_then(|a| ctx.dragon(slot).defend(a)) {
+                None => return MoveResult::Failed,
+                Some(attack) => attack,
+            };
+            if self.accuracy.is_some() && !ctx.accuracy_check(&attack) {
+                return MoveResult::Missed;
+            }
+        }
+        let target = |target: RelativePartyId| match target {
+            RelativePartyId::User => RelativePartyId::User,
+            RelativePartyId::Opposing => slot,
+        };
+
+        for effect in &self.effects {
+            match effect {
+                StatusMoveEffect::Protect => {
+                    let streak = match ctx.user.last_move() {
+                        Some(name) if name == self.name => ctx.user.move_streak(),
+                        _ => 0,
+                    };
+                    let chance = 3u32.saturating_pow(streak as u32);
+                    if !ctx.rng.gen_ratio(1, chance) {
+                        return MoveResult::Failed;
+                    }
+                }
+                StatusMoveEffect::Substitute if ctx.user.has_effect("substitute") => {
+                    return MoveResult::Failed;
+                }
+                _ => {}
+            }
+        }
+
+        let mut events = vec![];
+        for effect in &self.effects {
+            match effect {
+                StatusMoveEffect::Stage {
+                    target: stage_target,
+                    stat,
+                    amount,
+                } => {
+                    let kind = SecondaryEffectKind::Stage {
+                        stat: *stat,
+                        amount: *amount,
+                    };
+                    let secondary = SecondaryEffect::new(100, target(*stage_target), kind);
+                    events.extend(ctx.apply_secondary_effects(&[secondary]));
+                }
+                StatusMoveEffect::Status(status) => {
+                    let kind = SecondaryEffectKind::Status(*status);
+                    let secondary =
+                        SecondaryEffect::new(100, target(RelativePartyId::Opposing), kind);
+                    events.extend(ctx.apply_secondary_effects(&[secondary]));
+                }
+                StatusMoveEffect::Volatile(condition) => {
+                    let kind = SecondaryEffectKind::Volatile(*condition);
+                    let secondary =
+                        SecondaryEffect::new(100, target(RelativePartyId::Opposing), kind);
+                    events.extend(ctx.apply_secondary_effects(&[secondary]));
+                }
+                StatusMoveEffect::Heal { percent } => {
+                    let amount = percent_of(ctx.user.dragon.max_hp(), *percent);
+                    events.push(MoveEvent::Healed {
+                        rel_party_id: RelativePartyId::User,
+                        heal_amount: ctx.user.heal(amount),
+                    });
+                }
+                StatusMoveEffect::SideEffect {
+                    target: side_target,
+                    effect,
+                } => {
+                    let effect = effect.effect();
+                    events.push(MoveEvent::Effected {
+                        rel_party_id: target(*side_target),
+                        description: effect.get_name().to_string(),
+                    });
+                    ctx.add_side_effect(target(*side_target), effect);
+                }
+                StatusMoveEffect::Weather(weather) => {
+                    events.push(MoveEvent::Message {
+                        rel_party_id: RelativePartyId::User,
+                        message: format!("made it {}", weather.name),
+                    });
+                    ctx.set_weather(weather.clone());
+                }
+                StatusMoveEffect::Protect => {
+                    ctx.add_effect(RelativePartyId::User, Box::new(Protect))
+                }
+                StatusMoveEffect::Substitute => {
+                    let hp = ctx.user.dragon.max_hp() / 4;
+                    ctx.add_effect(RelativePartyId::User, Box::new(Substitute::new(hp)))
+                }
+                StatusMoveEffect::Redirect { slot } => {
+                    ctx.add_effect(RelativePartyId::User, Box::new(Redirect::new(*slot)))
+                }
+            }
+        }
+        if self.targets_opponent() && slot == RelativePartyId::Opposing {
+            MoveResult::Hit(events)
+        } else {
+            MoveResult::Succeeded(events)
+        }
+    }
+
+    fn is_status(&self) -> bool {
+        true
     }
 
     fn get_name(&self) -> &str {
@@ -1,13 +1,33 @@
-use std::fmt::Display;
+use std::{cmp::min, fmt::Display, sync::Arc};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    dragon::{BattleDragon, StatStages, Stats},
-    effect::LongTermEffectTrait,
-    moves::{calculate_static_damage, MoveStats},
+    ability::{Ability, AbilityData},
+    damage,
+    dragon::{BattleDragon, Stat, StatStages, Stats},
+    effect::{
+        effects::StatStageModifier, EffectAction, EffectCategory, EffectContext,
+        LongTermEffectTrait,
+    },
+    error::{EngineError, IllegalAction, Result},
+    events::MoveEvent,
+    item::{HeldItem, ItemData},
+    moves::{AttackContext, AttackFlags, Interception, MoveSlot, MoveStats, MoveTrait},
 };
 
 pub struct PartyItem {
     pub(crate) dragon: BattleDragon,
+    pub(crate) ability: Option<AbilityData>,
+    pub(crate) item: Option<ItemData>,
+    pub(crate) moves: Vec<MoveSlot>,
+    /// The name of the last move the dragon used, with the number of
+    /// times in a row it succeeded.
+    pub(crate) last_move: Option<(String, u8)>,
+    /// The attached effects with the number of turns they have been
+    /// attached for, sorted by descending priority. Every fold over
+    /// the effects goes in this order.
     pub(crate) effects: Vec<(u16, Box<dyn LongTermEffectTrait>)>,
 }
 
@@ -15,10 +35,102 @@ impl PartyItem {
     pub fn new(dragon: BattleDragon) -> Self {
         Self {
             dragon,
+            ability: None,
+            item: None,
+            moves: vec![],
+            last_move: None,
             effects: vec![],
         }
     }
 
+    /// Gives the dragon an ability, which is attached as a
+    /// permanent effect once the dragon enters the battle.
+    pub fn with_ability(mut self, ability: AbilityData) -> Self {
+        self.ability = Some(ability);
+        self
+    }
+
+    pub fn ability(&self) -> Option<&AbilityData> {
+        self.ability.as_ref()
+    }
+
+    /// Gives the dragon an item to hold.
+    pub fn with_item(mut self, item: ItemData) -> Self {
+        self.give_item(item);
+        self
+    }
+
+    pub fn item(&self) -> Option<&ItemData> {
+        self.item.as_ref()
+    }
+
+    /// Takes the held item away from the dragon, detaching its effect.
+    pub fn take_item(&mut self) -> Option<ItemData> {
+        self.effects.retain(|(_, e)| e.get_name() != "held_item");
+        self.item.take()
+    }
+
+    /// Gives the dragon an item to hold, and attaches its effect.
+    /// Returns the item the dragon held before.
+    pub fn give_item(&mut self, item: ItemData) -> Option<ItemData> {
+        let previous = self.take_item();
+        self.add_effect(Box::new(HeldItem::new(item.clone())));
+        self.item = Some(item);
+        previous
+    }
+
+    /// Teaches the dragon a move with the given number of power points.
+    pub fn with_move(mut self, attack: Arc<dyn MoveTrait>, max_pp: u8) -> Self {
+        self.moves.push(MoveSlot::new(attack, max_pp));
+        self
+    }
+
+    /// Teaches the dragon a move. It replaces the move in the slot
+    /// `replace` if given, otherwise it is added. Returns the replaced
+    /// move, or an error without teaching anything if there is no move
+    /// in the slot.
+    pub fn learn_move(
+        &mut self,
+        attack: Arc<dyn MoveTrait>,
+        max_pp: u8,
+        replace: Option<usize>,
+    ) -> Result<Option<MoveSlot>> {
+        let slot = MoveSlot::new(attack, max_pp);
+        match replace {
+            Some(index) => {
+                let replaced = self
+                    .moves
+                    .get_mut(index)
+                    .ok_or(IllegalAction::NoSuchMove(index))?;
+                Ok(Some(std::mem::replace(replaced, slot)))
+            }
+            None => {
+                self.moves.push(slot);
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn moves(&self) -> &[MoveSlot] {
+        &self.moves
+    }
+
+    pub fn moves_mut(&mut self) -> &mut [MoveSlot] {
+        &mut self.moves
+    }
+
+    /// Restores `amount` PP of every move. Returns true if any
+    /// PP was restored.
+    pub fn restore_pp(&mut self, amount: u8) -> bool {
+        let mut restored = false;
+        for slot in &mut self.moves {
+            let pp = min(slot.max_pp, slot.pp.saturating_add(amount));
+            restored |= pp != slot.pp;
+            slot.pp = pp;
+        }
+        restored
+    }
+
     pub fn hp(&self) -> u32 {
         self.dragon.hp
     }
@@ -30,66 +142,340 @@ impl PartyItem {
     }
 
     pub fn calc_stats(&self) -> Stats {
-        self.dragon.stats().apply_stages(self.calc_stages())
+        self.calc_stats_with(self.calc_stages())
+    }
+
+    /// Calculates the stats of the dragon with the given stat stages.
+    pub fn calc_stats_with(&self, stages: StatStages) -> Stats {
+        self.effects
+            .iter()
+            .fold(self.dragon.stats().apply_stages(stages), |s, (_, e)| {
+                e.stat_modification(s)
+            })
     }
 
     pub fn calc_damage(&self, base_power: u32, opponent_defense: u32) -> u32 {
-        calculate_static_damage(self.calc_stats().attack, opponent_defense, base_power)
+        damage::base_damage(
+            self.dragon.level(),
+            self.calc_stats().attack,
+            opponent_defense,
+            base_power,
+        )
     }
 
-    /// Returns None if an effect does not allow the operation to progress.
-    pub fn defend(
+    /// Passes an attack against this dragon through the `defending`
+    /// hooks of its effects. Returns None if an effect does not allow
+    /// the attack to progress.
+    pub fn defend(&self, attack: AttackContext) -> Option<AttackContext> {
+        self.effects
+            .iter()
+            .try_fold(attack, |a, (_, e)| e.defending(a))
+    }
+
+    /// Calculates the attack of this dragon against `defender` with a
+    /// move. The stat stages of both dragons are calculated, then passed
+    /// through the `offending` hooks of this dragon's effects, the
+    /// `intercept` hooks and finally the `defending` hooks of the
+    /// defender's effects, in this order. Returns None if an effect does
+    /// not allow the attack to progress.
+    pub fn offend(
         &self,
         move_stats: MoveStats,
-        opponent_stages: StatStages,
-    ) -> Option<(StatStages, MoveStats, StatStages)> {
-        self.effects.iter().fold(
-            Some((StatStages::new(), move_stats, opponent_stages)),
-            |s, (_, e)| s.and_then(|(u, m, o)| e.defending(u, m, o)),
-        )
+        flags: AttackFlags,
+        defender: &PartyItem,
+    ) -> Option<AttackContext> {
+        let attack = self.prepare_attack(move_stats, flags, defender)?;
+        if defender.intercept(&attack).is_some() {
+            return None;
+        }
+        defender.defend(attack)
     }
 
-    /// Calculate (potentially modify) the stat stages and the move stats for
-    /// attacking. Also calls the closure given in `defender` on the calculated
-    /// stats. Usually, `offend` should be called like this:
-    /// ```text
-    /// user.offend(move_stats, opponent.calc_stages(), |(m, o)| opponent.defend(m, o))
-    /// ```
-    ///
-    pub fn offend<T>(
+    /// Calculates the attack of this dragon against `defender` up to the
+    /// point where it is aimed at the defender: only the `offending`
+    /// hooks of this dragon's effects are called.
+    pub fn prepare_attack(
         &self,
         move_stats: MoveStats,
-        opponent_stages: StatStages,
-        defender: T,
-    ) -> Option<(StatStages, MoveStats, StatStages)>
-    where
-        T: FnOnce(MoveStats, StatStages) -> Option<(StatStages, MoveStats, StatStages)>,
-    {
-        let (stages, move_stats, opponent_stages) = self.effects.iter().fold(
-            Some((self.calc_stages(), move_stats, opponent_stages)),
-            |s, (_, e)| s.and_then(|(u, m, o)| e.offending(u, m, o)),
-        )?;
-        let (powered_opponent_stages, move_stats, stages) = defender(move_stats, stages)?;
-        Some((
-            stages,
+        flags: AttackFlags,
+        defender: &PartyItem,
+    ) -> Option<AttackContext> {
+        let attack = AttackContext::new(
+            self.calc_stages(),
+            defender.calc_stages(),
             move_stats,
-            opponent_stages + powered_opponent_stages,
-        ))
+            flags,
+        );
+        self.effects
+            .iter()
+            .try_fold(attack, |a, (_, e)| e.offending(a))
+    }
+
+    /// Asks the effects of this dragon whether an attack aimed at it
+    /// reaches it. Returns the display name of the first effect stopping
+    /// the attack and what it does with the attack, or None if it hits.
+    pub fn intercept(&self, attack: &AttackContext) -> Option<(&str, Interception)> {
+        self.effects
+            .iter()
+            .map(|(_, e)| (e.display_name(), e.intercept(attack)))
+            .find(|(_, i)| *i != Interception::Hit)
+    }
+
+    /// Lets the effects of the dragon absorb the damage of a hit.
+    /// Returns the damage left for the HP of the dragon.
+    pub fn absorb(&mut self, damage: u32) -> u32 {
+        self.effects
+            .iter_mut()
+            .fold(damage, |d, (_, e)| e.absorb(d))
+    }
+
+    /// The name of the last move the dragon used.
+    pub fn last_move(&self) -> Option<&str> {
+        self.last_move.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// The number of times in a row the last move of the dragon succeeded.
+    pub fn move_streak(&self) -> u8 {
+        self.last_move.as_ref().map_or(0, |(_, streak)| *streak)
+    }
+
+    pub(crate) fn record_move(&mut self, name: &str, succeeded: bool) {
+        let streak = match &self.last_move {
+            Some((last, streak)) if last == name && succeeded => streak.saturating_add(1),
+            _ if succeeded => 1,
+            _ => 0,
+        };
+        self.last_move = Some((name.to_string(), streak));
     }
 
     pub fn may_switch(&self) -> bool {
         self.effects
             .iter()
-            .fold(Some(()), |s, (_, e)| s.and_then(|_| e.switching()))
+            .try_fold((), |_, (_, e)| e.switching())
             .is_some()
     }
 
+    /// Adds an effect to the dragon outside of a battle. Only the
+    /// requests of the effect targeting the dragon itself are carried out.
+    /// Effects rolling for something always get the same rolls, see
+    /// [`add_effect_with_rng`](PartyItem::add_effect_with_rng).
     pub fn add_effect(&mut self, effect: Box<dyn LongTermEffectTrait>) {
-        let (attach, dragon) = effect.apply(self.dragon);
-        self.dragon = dragon;
+        self.add_effect_with_rng(effect, &mut StdRng::seed_from_u64(0));
+    }
+
+    /// Adds an effect to the dragon outside of a battle like
+    /// [`add_effect`](PartyItem::add_effect), with the rolls of the
+    /// effect decided by `rng`.
+    pub fn add_effect_with_rng(
+        &mut self,
+        effect: Box<dyn LongTermEffectTrait>,
+        rng: &mut dyn RngCore,
+    ) {
+        let actions = self.attach(effect, None, rng);
+        self.run_local_actions(actions, rng);
+    }
+
+    /// Detaches the modifiers of the stage of `stat`. Returns a single
+    /// modifier changing it by their sum and `amount`.
+    fn merge_stages(&mut self, stat: Stat, amount: i8) -> Box<dyn LongTermEffectTrait> {
+        let mut stages = StatStages::new();
+        self.effects.retain(|(_, e)| match e.stage_change() {
+            Some((other, previous)) if other == stat => {
+                stages = stages.modify(stat, previous);
+                false
+            }
+            _ => true,
+        });
+        Box::new(StatStageModifier::new(
+            stat,
+            stages.modify(stat, amount).get(stat),
+        ))
+    }
+
+    /// Calls `apply` on the effect, and attaches it if it wants to
+    /// stay. Returns the actions requested by the effect.
+    pub(crate) fn attach(
+        &mut self,
+        effect: Box<dyn LongTermEffectTrait>,
+        opponent: Option<&PartyItem>,
+        rng: &mut dyn RngCore,
+    ) -> Vec<EffectAction> {
+        let name = effect.get_name();
+        if !self.effects.iter().all(|(_, e)| e.accepts_effect(name)) {
+            return vec![EffectAction::Event(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: format!("is immune to {}", name),
+            })];
+        }
+        if effect.category() == EffectCategory::Status && self.has_status() {
+            return vec![EffectAction::Event(MoveEvent::Message {
+                rel_party_id: RelativePartyId::User,
+                message: "already has a status condition".to_string(),
+            })];
+        }
+        let effect = match effect.stage_change() {
+            Some((stat, amount)) => self.merge_stages(stat, amount),
+            None => effect,
+        };
+        let mut ctx = EffectContext::new(&self.dragon, opponent, rng);
+        let attach = effect.apply(&mut ctx);
+        let actions = ctx.into_actions();
         if attach {
-            self.effects.push((0, effect));
+            let index = self
+                .effects
+                .iter()
+                .position(|(_, e)| e.priority() < effect.priority())
+                .unwrap_or(self.effects.len());
+            self.effects.insert(index, (0, effect));
+        }
+        actions
+    }
+
+    /// Detaches every effect with the given name, except the ones
+    /// that can not be removed. Returns true if anything was removed.
+    pub fn remove_effect(&mut self, name: &str) -> bool {
+        let before = self.effects.len();
+        self.effects
+            .retain(|(_, e)| e.get_name() != name || !e.removable());
+        before != self.effects.len()
+    }
+
+    /// Detaches every effect of the category, except the ones that can
+    /// not be removed. Returns true if anything was removed.
+    pub fn remove_category(&mut self, category: EffectCategory) -> bool {
+        let before = self.effects.len();
+        self.effects
+            .retain(|(_, e)| e.category() != category || !e.removable());
+        before != self.effects.len()
+    }
+
+    /// Detaches every effect that can be removed.
+    pub fn clear_effects(&mut self) {
+        self.effects.retain(|(_, e)| !e.removable());
+    }
+
+    pub fn has_effect(&self, name: &str) -> bool {
+        self.effects.iter().any(|(_, e)| e.get_name() == name)
+    }
+
+    /// Returns the display name of the first effect forbidding the move
+    /// in a move slot, such as a disable, a taunt or a choice item.
+    pub fn blocking_effect(&self, slot: usize) -> Option<&str> {
+        let attack = self.moves.get(slot)?.attack();
+        self.effects
+            .iter()
+            .find(|(_, e)| !e.allows_move(attack))
+            .map(|(_, e)| e.display_name())
+    }
+
+    /// Returns true if the move in a move slot has PP left, and no
+    /// effect of the dragon forbids it.
+    pub fn can_use_move(&self, slot: usize) -> bool {
+        self.moves.get(slot).is_some_and(|m| m.pp > 0) && self.blocking_effect(slot).is_none()
+    }
+
+    /// Returns the move slots the dragon may choose from.
+    pub fn usable_moves(&self) -> Vec<usize> {
+        (0..self.moves.len())
+            .filter(|&slot| self.can_use_move(slot))
+            .collect()
+    }
+
+    /// Returns the move slot the dragon has to use in its next turn,
+    /// if one of its effects forces a move. Fainted dragons have no
+    /// next turn, so they are never forced.
+    pub fn forced_move(&self) -> Option<usize> {
+        if self.hp() == 0 {
+            return None;
         }
+        let name = self.effects.iter().find_map(|(_, e)| e.forced_move())?;
+        self.moves.iter().position(|m| m.attack.get_name() == name)
+    }
+
+    /// Returns true if the dragon has a major status condition.
+    pub fn has_status(&self) -> bool {
+        self.effects
+            .iter()
+            .any(|(_, e)| e.category() == EffectCategory::Status)
+    }
+
+    /// Attaches the ability of the dragon, if it has one which
+    /// is not attached yet.
+    pub(crate) fn enter_battle(
+        &mut self,
+        opponent: &PartyItem,
+        rng: &mut dyn RngCore,
+    ) -> Vec<EffectAction> {
+        match &self.ability {
+            Some(ability) if !self.has_effect("ability") => {
+                let ability = Box::new(Ability::new(ability.clone()));
+                self.attach(ability, Some(opponent), rng)
+            }
+            _ => vec![],
+        }
+    }
+
+    fn run_local_actions(&mut self, actions: Vec<EffectAction>, rng: &mut dyn RngCore) {
+        for action in actions {
+            match action {
+                EffectAction::Damage(RelativePartyId::User, amount) => {
+                    self.damage(amount);
+                }
+                EffectAction::Heal(RelativePartyId::User, amount) => {
+                    self.heal(amount);
+                }
+                EffectAction::AddEffect(RelativePartyId::User, effect) => {
+                    self.add_effect_with_rng(effect, rng)
+                }
+                EffectAction::RemoveItem(RelativePartyId::User) => {
+                    self.take_item();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Calls `hook` on every effect in order, with a context seeing
+    /// `opponent` as the opposing dragon. Stops at the first effect
+    /// for which the hook returns false. Returns whether every hook
+    /// returned true and the actions requested by the effects.
+    pub(crate) fn run_hooks<F>(
+        &mut self,
+        opponent: &PartyItem,
+        rng: &mut dyn RngCore,
+        mut hook: F,
+    ) -> (bool, Vec<EffectAction>)
+    where
+        F: FnMut(&mut dyn LongTermEffectTrait, &mut EffectContext) -> bool,
+    {
+        let mut ctx = EffectContext::new(&self.dragon, Some(opponent), rng);
+        let passed = self
+            .effects
+            .iter_mut()
+            .all(|(_, e)| hook(e.as_mut(), &mut ctx));
+        (passed, ctx.into_actions())
+    }
+
+    /// Restores the HP of the dragon by `amount`, up to its maximum
+    /// HP. Returns the HP actually restored. Fainted dragons can only
+    /// be brought back with [`revive`](PartyItem::revive).
+    pub fn heal(&mut self, amount: u32) -> u32 {
+        if self.dragon.hp == 0 {
+            return 0;
+        }
+        let healed = amount.min(self.dragon.max_hp().saturating_sub(self.dragon.hp));
+        self.dragon.hp += healed;
+        healed
+    }
+
+    /// Brings a fainted dragon back with `hp` HP, at least 1. Returns
+    /// false if the dragon has not fainted.
+    pub fn revive(&mut self, hp: u32) -> bool {
+        if self.dragon.hp > 0 {
+            return false;
+        }
+        self.dragon.hp = hp.clamp(1, self.dragon.max_hp());
+        true
     }
 
     /// Reduces the HP of the dragon by `amount`. Returns
@@ -108,26 +494,19 @@ impl PartyItem {
     /// Starts a new turn. All effects will be
     /// notified, and possibly detached. Effect
     /// duration counters are incremented by one.
-    pub fn turn(&mut self) {
-        let mut removed = Vec::new();
-        let mut attached = Vec::new();
-        for (idx, (duration, effect)) in self.effects.iter_mut().enumerate() {
-            let (keep, added_effects) = effect.turn(*duration);
-            if !keep {
-                removed.push(idx);
-            }
-            if let Some(mut effects) = added_effects {
-                attached.append(&mut effects);
-            }
-        }
-
-        for idx in removed.iter().rev() {
-            self.effects.remove(*idx);
-        }
-
-        for effect in attached {
-            self.add_effect(effect);
-        }
+    /// Returns the actions requested by the effects.
+    pub(crate) fn turn(
+        &mut self,
+        opponent: &PartyItem,
+        rng: &mut dyn RngCore,
+    ) -> Vec<EffectAction> {
+        let mut ctx = EffectContext::new(&self.dragon, Some(opponent), rng);
+        self.effects.retain_mut(|(duration, effect)| {
+            let keep = effect.turn(&mut ctx, *duration);
+            *duration += 1;
+            keep
+        });
+        ctx.into_actions()
     }
 }
 
@@ -136,7 +515,7 @@ pub struct Party {
     pub(crate) active: usize,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PartyId {
     Party1,
     Party2,
@@ -159,11 +538,11 @@ impl PartyId {
         }
     }
 
-    pub fn from_u8(party_id: u8) -> Option<Self> {
+    pub fn from_u8(party_id: u8) -> Result<Self> {
         match party_id {
-            0 => Some(PartyId::Party1),
-            1 => Some(PartyId::Party2),
-            _ => None,
+            0 => Ok(PartyId::Party1),
+            1 => Ok(PartyId::Party2),
+            _ => Err(EngineError::InvalidPartyId(party_id)),
         }
     }
 }
@@ -183,21 +562,37 @@ impl From<PartyId> for u8 {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RelativePartyId {
     User,
     Opposing,
 }
 
+impl RelativePartyId {
+    pub fn opposing(self) -> Self {
+        match self {
+            RelativePartyId::User => RelativePartyId::Opposing,
+            RelativePartyId::Opposing => RelativePartyId::User,
+        }
+    }
+}
+
 impl Party {
-    pub fn new_empty() -> Self {
+    /// Creates a party with a single member, which is active. A party
+    /// always has at least one member, so it always has an active dragon.
+    pub fn new(first: PartyItem) -> Self {
         Self {
-            items: vec![],
+            items: vec![first],
             active: 0,
         }
     }
 
-    pub fn new_from_vec(items: Vec<PartyItem>) -> Self {
-        Self { items, active: 0 }
+    /// Creates a party from its members. The first one is active.
+    pub fn new_from_vec(items: Vec<PartyItem>) -> Result<Self> {
+        if items.is_empty() {
+            return Err(EngineError::EmptyParty);
+        }
+        Ok(Self { items, active: 0 })
     }
 
     /// Gets a reference to the active dragon.
@@ -210,16 +605,55 @@ impl Party {
         &mut self.items[self.active]
     }
 
-    /// Returns false if the switch operation was canceled by an active effect.
-    pub fn switch(&mut self, next: usize) -> bool {
-        if self.active().may_switch() {
-            self.active = next;
-            true
-        } else {
-            false
+    /// Gets the index of the active dragon.
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Gets a reference to the party member at `index`.
+    pub fn member(&self, index: usize) -> Option<&PartyItem> {
+        self.items.get(index)
+    }
+
+    pub fn members(&self) -> &[PartyItem] {
+        &self.items
+    }
+
+    pub fn member_mut(&mut self, index: usize) -> Option<&mut PartyItem> {
+        self.items.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns why the party can not switch to the member at `next`,
+    /// if it can not. A fainted active dragon can always be replaced,
+    /// even if it was trapped.
+    pub fn check_switch(&self, next: usize) -> std::result::Result<(), IllegalAction> {
+        let active = self.active();
+        match self.member(next) {
+            None => Err(IllegalAction::NoSuchMember(next)),
+            Some(_) if next == self.active => Err(IllegalAction::AlreadyActive(next)),
+            Some(member) if member.hp() == 0 => Err(IllegalAction::Fainted(next)),
+            Some(_) if active.hp() > 0 && !active.may_switch() => Err(IllegalAction::Trapped),
+            Some(_) => Ok(()),
         }
     }
 
+    /// Makes the member at `next` active. Fails if the member does not
+    /// exist, is already active or has fainted, or if an effect of the
+    /// active dragon stops it from switching out.
+    pub fn switch(&mut self, next: usize) -> Result<()> {
+        self.check_switch(next)?;
+        self.active = next;
+        Ok(())
+    }
+
     pub fn add_dragon(&mut self, dragon: BattleDragon) {
         self.items.push(PartyItem::new(dragon));
     }
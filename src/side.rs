@@ -0,0 +1,293 @@
+use std::num::NonZeroU32;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{dragon::Stats, moves::MoveStats, party::PartyItem};
+
+/// An effect that belongs to one side of the battlefield instead of a
+/// single dragon, so it stays in place when the active dragon is switched.
+/// Like with [`LongTermEffectTrait`](crate::effect::LongTermEffectTrait),
+/// every callback returns the (possibly modified) data it was given.
+///
+/// Side effects are called in order of their priority, highest first,
+/// and in the order they were added in when the priorities are equal.
+pub trait SideEffectTrait: Send + Sync {
+    /// Return a lowercase string corresponding to the type
+    /// of the side effect.
+    fn get_name(&self) -> &str;
+
+    /// The priority of the effect, which decides where it is placed
+    /// among the other effects of the side.
+    fn priority(&self) -> i8 {
+        0
+    }
+
+    /// Called each turn with the number of turns the effect has been
+    /// active. Returns false if it should be detached.
+    fn turn(&mut self, _turn: u16) -> bool {
+        true
+    }
+
+    /// Called when a dragon is switched in on this side. Returns the
+    /// amount of damage the dragon takes on entry.
+    fn switch_in(&self, _dragon: &PartyItem) -> u32 {
+        0
+    }
+
+    /// Called when the final stats of the active dragon on this side
+    /// are being calculated.
+    fn stat_calculation(&self, stats: Stats) -> Stats {
+        stats
+    }
+
+    /// Called when a dragon on this side is about to take `damage`
+    /// from a move.
+    fn defending(&self, damage: u32, _move_stats: &MoveStats) -> u32 {
+        damage
+    }
+
+    /// Returns true if the effect is a screen, whose damage reduction
+    /// critical hits may ignore, see
+    /// [`CritRules::ignore_screens`](crate::damage::CritRules::ignore_screens).
+    fn is_screen(&self) -> bool {
+        false
+    }
+}
+
+/// A side effect as loaded from data, for example as part of a move.
+/// Denominators of zero are rejected when the data is read.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SideEffectData {
+    Screen {
+        turns: u16,
+        numerator: u32,
+        denominator: NonZeroU32,
+    },
+    EntryHazard {
+        denominator: NonZeroU32,
+    },
+    Tailwind {
+        turns: u16,
+    },
+}
+
+impl SideEffectData {
+    pub fn effect(&self) -> Box<dyn SideEffectTrait> {
+        match *self {
+            SideEffectData::Screen {
+                turns,
+                numerator,
+                denominator,
+            } => Box::new(effects::Screen::new_ratio(turns, numerator, denominator)),
+            SideEffectData::EntryHazard { denominator } => {
+                Box::new(effects::EntryHazard::new(denominator))
+            }
+            SideEffectData::Tailwind { turns } => Box::new(effects::Tailwind::new(turns)),
+        }
+    }
+}
+
+/// Weather covering the whole battlefield for a number of turns. It
+/// strengthens the moves of one type and weakens the moves of another.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WeatherData {
+    pub name: String,
+    pub turns: u16,
+    /// Moves of this type deal 3/2 of their damage.
+    #[serde(default)]
+    pub boosted_type: Option<String>,
+    /// Moves of this type deal half of their damage.
+    #[serde(default)]
+    pub weakened_type: Option<String>,
+}
+
+/// The side effects active on one side of the battlefield.
+#[derive(Default)]
+pub struct SideEffects {
+    effects: Vec<(u16, Box<dyn SideEffectTrait>)>,
+}
+
+impl SideEffects {
+    pub fn new() -> Self {
+        Self { effects: vec![] }
+    }
+
+    pub fn add_effect(&mut self, effect: Box<dyn SideEffectTrait>) {
+        let index = self
+            .effects
+            .iter()
+            .position(|(_, e)| e.priority() < effect.priority())
+            .unwrap_or(self.effects.len());
+        self.effects.insert(index, (0, effect));
+    }
+
+    /// Returns true if an effect with the given name is active.
+    pub fn contains(&self, name: &str) -> bool {
+        self.effects.iter().any(|(_, e)| e.get_name() == name)
+    }
+
+    /// Returns the number of active effects with the given name, for
+    /// effects that can be stacked (such as entry hazards).
+    pub fn count(&self, name: &str) -> usize {
+        self.effects
+            .iter()
+            .filter(|(_, e)| e.get_name() == name)
+            .count()
+    }
+
+    /// Detaches every effect with the given name. Returns true if
+    /// anything was removed.
+    pub fn remove_effect(&mut self, name: &str) -> bool {
+        let before = self.effects.len();
+        self.effects.retain(|(_, e)| e.get_name() != name);
+        before != self.effects.len()
+    }
+
+    /// Sums the entry damage of all effects for a dragon switching in.
+    pub fn switch_in(&self, dragon: &PartyItem) -> u32 {
+        self.effects.iter().map(|(_, e)| e.switch_in(dragon)).sum()
+    }
+
+    pub fn calc_stats(&self, stats: Stats) -> Stats {
+        self.effects
+            .iter()
+            .fold(stats, |s, (_, e)| e.stat_calculation(s))
+    }
+
+    /// Applies the effects to the damage of a move, skipping the
+    /// screens if `ignore_screens` is set.
+    pub fn defend(&self, damage: u32, move_stats: &MoveStats, ignore_screens: bool) -> u32 {
+        self.effects
+            .iter()
+            .filter(|(_, e)| !(ignore_screens && e.is_screen()))
+            .fold(damage, |d, (_, e)| e.defending(d, move_stats))
+    }
+
+    /// Starts a new turn. All effects will be notified, and possibly
+    /// detached. Effect duration counters are incremented by one.
+    pub fn turn(&mut self) {
+        self.effects.retain_mut(|(duration, effect)| {
+            let keep = effect.turn(*duration);
+            *duration += 1;
+            keep
+        });
+    }
+}
+
+pub mod effects {
+    use std::num::NonZeroU32;
+
+    use crate::{dragon::Stats, moves::MoveStats, party::PartyItem};
+
+    use super::{SideEffectTrait, WeatherData};
+
+    /// Multiplies the damage taken by dragons on the side by
+    /// `numerator / denominator` for a number of turns. The default
+    /// screen halves all damage.
+    pub struct Screen {
+        turns: u16,
+        numerator: u32,
+        denominator: NonZeroU32,
+    }
+    impl Screen {
+        pub fn new(turns: u16) -> Self {
+            Self::new_ratio(turns, 1, NonZeroU32::new(2).unwrap())
+        }
+
+        pub fn new_ratio(turns: u16, numerator: u32, denominator: NonZeroU32) -> Self {
+            Self {
+                turns,
+                numerator,
+                denominator,
+            }
+        }
+    }
+    impl SideEffectTrait for Screen {
+        fn get_name(&self) -> &str {
+            "screen"
+        }
+
+        fn turn(&mut self, turn: u16) -> bool {
+            turn + 1 < self.turns
+        }
+
+        fn defending(&self, damage: u32, _move_stats: &MoveStats) -> u32 {
+            damage * self.numerator / self.denominator
+        }
+
+        fn is_screen(&self) -> bool {
+            true
+        }
+    }
+
+    /// Damages every dragon switching in on the side by
+    /// `1 / denominator` of its maximum HP. Stays until removed.
+    pub struct EntryHazard(NonZeroU32);
+    impl EntryHazard {
+        pub fn new(denominator: NonZeroU32) -> Self {
+            Self(denominator)
+        }
+    }
+    impl SideEffectTrait for EntryHazard {
+        fn get_name(&self) -> &str {
+            "entry_hazard"
+        }
+
+        fn switch_in(&self, dragon: &PartyItem) -> u32 {
+            dragon.dragon.max_hp() / self.0
+        }
+    }
+
+    /// Doubles the speed of the dragons on the side for a number of turns.
+    pub struct Tailwind(u16);
+    impl Tailwind {
+        pub fn new(turns: u16) -> Self {
+            Self(turns)
+        }
+    }
+    impl SideEffectTrait for Tailwind {
+        fn get_name(&self) -> &str {
+            "tailwind"
+        }
+
+        fn turn(&mut self, turn: u16) -> bool {
+            turn + 1 < self.0
+        }
+
+        fn stat_calculation(&self, stats: Stats) -> Stats {
+            Stats {
+                speed: stats.speed * 2,
+                ..stats
+            }
+        }
+    }
+
+    /// Weather on the battlefield. The battlefield adds it to both
+    /// sides, so each side changes the damage its dragons take.
+    pub struct Weather(WeatherData);
+    impl Weather {
+        pub fn new(data: WeatherData) -> Self {
+            Self(data)
+        }
+    }
+    impl SideEffectTrait for Weather {
+        fn get_name(&self) -> &str {
+            "weather"
+        }
+
+        fn turn(&mut self, turn: u16) -> bool {
+            turn + 1 < self.0.turns
+        }
+
+        fn defending(&self, damage: u32, move_stats: &MoveStats) -> u32 {
+            let move_type = move_stats.move_type.as_ref();
+            if move_type.is_some() && move_type == self.0.boosted_type.as_ref() {
+                damage * 3 / 2
+            } else if move_type.is_some() && move_type == self.0.weakened_type.as_ref() {
+                damage / 2
+            } else {
+                damage
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::{fmt::Write, sync::Arc};
+
+use crate::{
+    ability::AbilityData,
+    data::{Dex, MoveEntry},
+    dragon::{BattleDragon, DragonData, Gender, Stats, MAX_VALUE},
+    error::{EngineError, Result},
+    item::ItemData,
+    party::{Party, PartyItem},
+};
+
+/// The names of the stats in the `Values` line, in the order they are written.
+const STAT_NAMES: [&str; 4] = ["HP", "Atk", "Def", "Spe"];
+
+/// A party member which is still being read.
+struct PendingMember {
+    species: Arc<DragonData>,
+    nickname: Option<String>,
+    gender: Gender,
+    cosmetics: Vec<String>,
+    item: Option<ItemData>,
+    ability: Option<AbilityData>,
+    level: Option<u8>,
+    values: Stats,
+    moves: Vec<MoveEntry>,
+}
+
+impl PendingMember {
+    /// Reads the first line of a member: `Nickname (Species) (M) @ Item`,
+    /// where the nickname, the gender and the item are optional.
+    fn parse_header(line: &str, dex: &Dex) -> std::result::Result<Self, String> {
+        let (name, item) = match line.split_once(" @ ") {
+            Some((name, item)) => (name.trim(), Some(item.trim())),
+            None => (line, None),
+        };
+        let (name, gender) = if let Some(name) = name.strip_suffix(" (M)") {
+            (name, Gender::Male)
+        } else if let Some(name) = name.strip_suffix(" (F)") {
+            (name, Gender::Female)
+        } else {
+            (name, Gender::Genderless)
+        };
+        let (nickname, species) = match name.strip_suffix(')').and_then(|n| n.rsplit_once(" (")) {
+            Some((nickname, species)) => (Some(nickname.trim().to_string()), species.trim()),
+            None => (None, name),
+        };
+        let species = dex
+            .species(species)
+            .ok_or_else(|| format!("unknown species \"{}\"", species))?;
+        let item = match item {
+            Some(item) => Some(
+                dex.item(item)
+                    .ok_or_else(|| format!("unknown item \"{}\"", item))?
+                    .clone(),
+            ),
+            None => None,
+        };
+        Ok(Self {
+            species: species.clone(),
+            nickname,
+            gender,
+            cosmetics: vec![],
+            item,
+            ability: None,
+            level: None,
+            values: Stats::zero(),
+            moves: vec![],
+        })
+    }
+
+    /// Reads a line after the first one.
+    fn parse_line(&mut self, line: &str, dex: &Dex) -> std::result::Result<(), String> {
+        if let Some(name) = line.strip_prefix('-') {
+            let name = name.trim();
+            let entry = dex
+                .get_move(name)
+                .ok_or_else(|| format!("unknown move \"{}\"", name))?;
+            self.moves.push(entry.clone());
+            return Ok(());
+        }
+        match line.split_once(':').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("Ability", name)) => {
+                let ability = dex
+                    .ability(name)
+                    .ok_or_else(|| format!("unknown ability \"{}\"", name))?;
+                self.ability = Some(ability.clone());
+            }
+            Some(("Level", level)) => match level.parse() {
+                Ok(level @ 1..=100) => self.level = Some(level),
+                _ => return Err(format!("invalid level \"{}\"", level)),
+            },
+            Some(("Values", values)) => self.values = parse_values(values)?,
+            Some(("Cosmetics", cosmetics)) => {
+                self.cosmetics = cosmetics.split(',').map(|c| c.trim().to_string()).collect()
+            }
+            _ => return Err(format!("unexpected line \"{}\"", line)),
+        }
+        Ok(())
+    }
+
+    fn build(self) -> PartyItem {
+        let mut dragon = BattleDragon::from_species(self.species)
+            .with_values(self.values)
+            .with_gender(self.gender);
+        for cosmetic in &self.cosmetics {
+            dragon = dragon.with_cosmetic(cosmetic);
+        }
+        if let Some(level) = self.level {
+            dragon = dragon.with_level(level);
+        }
+        if let Some(nickname) = &self.nickname {
+            dragon = dragon.with_nickname(nickname);
+        }
+        let mut item = PartyItem::new(dragon);
+        if let Some(held) = self.item {
+            item = item.with_item(held);
+        }
+        if let Some(ability) = self.ability {
+            item = item.with_ability(ability);
+        }
+        for entry in self.moves {
+            item = item.with_move(entry.attack, entry.max_pp);
+        }
+        item
+    }
+}
+
+/// Reads values such as `31 HP / 20 Atk`. Missing stats are zero, and no
+/// value may be above [`MAX_VALUE`].
+fn parse_values(text: &str) -> std::result::Result<Stats, String> {
+    let mut values = Stats::zero();
+    for part in text.split('/') {
+        let invalid = || format!("invalid values \"{}\"", part.trim());
+        let (value, stat) = part.trim().split_once(' ').ok_or_else(invalid)?;
+        let value = value
+            .parse()
+            .ok()
+            .filter(|&v| v <= MAX_VALUE)
+            .ok_or_else(invalid)?;
+        match stat.trim() {
+            "HP" => values.hp = value,
+            "Atk" => values.attack = value,
+            "Def" => values.defense = value,
+            "Spe" => values.speed = value,
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(values)
+}
+
+/// Reads a team from text, looking up the species, moves, items and
+/// abilities in the dex. Members are separated by blank lines:
+///
+/// ```text
+/// Sparky (Mew) (F) @ Leftovers
+/// Ability: Synchronize
+/// Level: 50
+/// Values: 31 HP / 31 Atk / 31 Def / 31 Spe
+/// Cosmetics: shiny
+/// - Pound
+/// - Psychic
+/// ```
+///
+/// Everything but the species is optional.
+pub fn import(text: &str, dex: &Dex) -> Result<Party> {
+    let mut members = vec![];
+    let mut pending: Option<PendingMember> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let parse_error = |message| EngineError::Parse {
+            line: index + 1,
+            message,
+        };
+        if line.is_empty() {
+            members.extend(pending.take().map(PendingMember::build));
+            continue;
+        }
+        match &mut pending {
+            Some(member) => member.parse_line(line, dex).map_err(parse_error)?,
+            None => pending = Some(PendingMember::parse_header(line, dex).map_err(parse_error)?),
+        }
+    }
+    members.extend(pending.map(PendingMember::build));
+    Party::new_from_vec(members)
+}
+
+/// Writes a team in the format read by [`import`]. Fails if a member
+/// has no species.
+pub fn export(party: &Party) -> Result<String> {
+    let mut text = String::new();
+    for (index, item) in party.members().iter().enumerate() {
+        let dragon = &item.dragon;
+        let species = dragon.species().ok_or(EngineError::MissingSpecies(index))?;
+        if index > 0 {
+            text.push('\n');
+        }
+        match dragon.nickname() {
+            Some(nickname) => write!(text, "{} ({})", nickname, species.name),
+            None => write!(text, "{}", species.name),
+        }
+        .unwrap();
+        match dragon.identity().gender {
+            Gender::Male => text.push_str(" (M)"),
+            Gender::Female => text.push_str(" (F)"),
+            Gender::Genderless => {}
+        }
+        if let Some(held) = item.item() {
+            write!(text, " @ {}", held.name).unwrap();
+        }
+        text.push('\n');
+        if let Some(ability) = item.ability() {
+            writeln!(text, "Ability: {}", ability.name).unwrap();
+        }
+        writeln!(text, "Level: {}", dragon.level()).unwrap();
+        let values = dragon.values();
+        let values = [values.hp, values.attack, values.defense, values.speed]
+            .iter()
+            .zip(STAT_NAMES.iter())
+            .filter(|(&value, _)| value > 0)
+            .map(|(value, stat)| format!("{} {}", value, stat))
+            .collect::<Vec<_>>();
+        if !values.is_empty() {
+            writeln!(text, "Values: {}", values.join(" / ")).unwrap();
+        }
+        let cosmetics = &dragon.identity().cosmetics;
+        if !cosmetics.is_empty() {
+            writeln!(text, "Cosmetics: {}", cosmetics.join(", ")).unwrap();
+        }
+        for move_slot in item.moves() {
+            writeln!(text, "- {}", move_slot.attack().get_name()).unwrap();
+        }
+    }
+    Ok(text)
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How effective the types of moves are against the types of dragons.
+/// Types are identified by their names; pairs missing from the chart
+/// are neutral.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TypeChart {
+    /// Maps an attacking type to the multipliers against defending types.
+    effectiveness: HashMap<String, HashMap<String, f32>>,
+}
+
+impl TypeChart {
+    pub fn new() -> Self {
+        Self {
+            effectiveness: HashMap::new(),
+        }
+    }
+
+    /// Sets the damage multiplier of an attacking type
+    /// against a defending type.
+    pub fn set(&mut self, attacking: &str, defending: &str, multiplier: f32) {
+        self.effectiveness
+            .entry(attacking.to_string())
+            .or_default()
+            .insert(defending.to_string(), multiplier);
+    }
+
+    /// Returns the damage multiplier of an attacking type
+    /// against a defending type.
+    pub fn multiplier(&self, attacking: &str, defending: &str) -> f32 {
+        self.effectiveness
+            .get(attacking)
+            .and_then(|m| m.get(defending))
+            .copied()
+            .unwrap_or(1.)
+    }
+
+    /// Returns the damage multiplier of an attacking type against
+    /// a dragon with all of the defending types.
+    pub fn against(&self, attacking: &str, defending: &[String]) -> f32 {
+        defending
+            .iter()
+            .map(|d| self.multiplier(attacking, d))
+            .product()
+    }
+}